@@ -0,0 +1,380 @@
+use crate::{Applicability, Diagnostic, Edit, Suggestion};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// Lines of unchanged context around each hunk in [`unified_diff`].
+const DIFF_CONTEXT: usize = 3;
+
+/// Suggestions chosen for application, plus the ones dropped because they
+/// overlap an edit that was already accepted.
+#[derive(Debug, Default)]
+pub struct FixSelection {
+    pub accepted: Vec<Suggestion>,
+    pub conflicts: Vec<Suggestion>,
+}
+
+impl FixSelection {
+    /// Accepted edits grouped by file, sorted by offset.
+    pub fn edits_by_file(&self) -> BTreeMap<String, Vec<Edit>> {
+        let mut by_file: BTreeMap<String, Vec<Edit>> = BTreeMap::new();
+        for edit in self.accepted.iter().flat_map(|s| &s.edits) {
+            by_file
+                .entry(edit.file.clone())
+                .or_default()
+                .push(edit.clone());
+        }
+        for edits in by_file.values_mut() {
+            edits.sort_by_key(|e| (e.byte_start, e.byte_end));
+        }
+        by_file
+    }
+
+    /// Accepted edits as a patch list sorted by file and offset: each edit's JSON
+    /// record plus the `applicability` of its suggestion. With a selection from
+    /// `select_fixes(diagnostics, false)`, it's exactly what `--apply` would do.
+    pub fn fix_plan(&self) -> Vec<Value> {
+        let mut edits: Vec<(&Edit, Applicability)> = (self.accepted.iter())
+            .flat_map(|s| s.edits.iter().map(move |e| (e, s.applicability)))
+            .collect();
+        edits.sort_by_key(|(e, _)| (&e.file, e.byte_start, e.byte_end));
+        edits
+            .into_iter()
+            .map(|(e, applicability)| {
+                let mut record = e.to_json();
+                record["applicability"] = json!(applicability.as_str());
+                record
+            })
+            .collect()
+    }
+}
+
+fn overlaps(a: &Edit, b: &Edit) -> bool {
+    a.file == b.file
+        && if a.byte_start == a.byte_end || b.byte_start == b.byte_end {
+            // Insertions conflict with anything touching the same point
+            a.byte_start <= b.byte_end && b.byte_start <= a.byte_end
+        } else {
+            a.byte_start < b.byte_end && b.byte_start < a.byte_end
+        }
+}
+
+/// Picks the suggestions to apply: MachineApplicable ones, plus MaybeIncorrect
+/// when `allow_maybe_incorrect` is set. Identical suggestions (e.g. reported for
+/// both the lib and lib-test targets) are kept once; a suggestion whose edits
+/// overlap an already accepted one is rejected as a whole.
+pub fn select_fixes(diagnostics: &[Diagnostic], allow_maybe_incorrect: bool) -> FixSelection {
+    let mut candidates: Vec<&Suggestion> = diagnostics
+        .iter()
+        .flat_map(|d| &d.suggestions)
+        .filter(|s| match s.applicability {
+            Applicability::MachineApplicable => true,
+            Applicability::MaybeIncorrect => allow_maybe_incorrect,
+            _ => false,
+        })
+        .filter(|s| !s.edits.is_empty())
+        .collect();
+    candidates.sort_by_key(|s| (s.applicability, &s.edits[0].file, s.edits[0].byte_start));
+
+    let mut selection = FixSelection::default();
+    for s in candidates {
+        if selection.accepted.iter().any(|a| a.edits == s.edits) {
+            continue;
+        }
+        let conflict = selection
+            .accepted
+            .iter()
+            .flat_map(|a| &a.edits)
+            .any(|accepted| s.edits.iter().any(|e| overlaps(accepted, e)));
+        if conflict {
+            selection.conflicts.push(s.clone());
+        } else {
+            selection.accepted.push(s.clone());
+        }
+    }
+    selection
+}
+
+/// Checks that `e` lies within `source` on char boundaries and doesn't start
+/// before `pos`, the end of the previous edit.
+fn check_fits(source: &str, e: &Edit, pos: usize) -> Result<(), String> {
+    if e.byte_start < pos
+        || e.byte_end < e.byte_start
+        || !source.is_char_boundary(e.byte_start)
+        || !source.is_char_boundary(e.byte_end)
+        || e.byte_end > source.len()
+    {
+        return Err(format!(
+            "edit {}..{} does not fit {} (file changed since cargo check?)",
+            e.byte_start, e.byte_end, e.file
+        ));
+    }
+    Ok(())
+}
+
+/// Applies non-overlapping `edits` (byte offsets into `source`) and returns the new text.
+/// Edits that fall outside the source or off a char boundary are an error, which
+/// usually means the file changed since cargo check ran.
+pub fn apply_edits(source: &str, edits: &[Edit]) -> Result<String, String> {
+    let mut sorted: Vec<&Edit> = edits.iter().collect();
+    sorted.sort_by_key(|e| (e.byte_start, e.byte_end));
+    let mut out = String::with_capacity(source.len());
+    let mut pos = 0;
+    for e in sorted {
+        check_fits(source, e, pos)?;
+        out.push_str(&source[pos..e.byte_start]);
+        out.push_str(&e.replacement);
+        pos = e.byte_end;
+    }
+    out.push_str(&source[pos..]);
+    Ok(out)
+}
+
+/// A contiguous block of old lines `[start, end)` replaced by `new_lines`.
+struct LineChange {
+    start: usize,
+    end: usize,
+    new_lines: Vec<String>,
+}
+
+/// Renders a unified diff of applying `edits` to `source`, using the edits
+/// themselves to find changed lines instead of diffing the whole file. Edits
+/// that don't fit the source are an error, as in [`apply_edits`].
+pub fn unified_diff(path: &str, source: &str, edits: &[Edit]) -> Result<String, String> {
+    let lines: Vec<&str> = source.split_inclusive('\n').collect();
+    let mut line_offsets = Vec::with_capacity(lines.len() + 1);
+    let mut offset = 0;
+    for l in &lines {
+        line_offsets.push(offset);
+        offset += l.len();
+    }
+    line_offsets.push(offset);
+    let line_of = |byte: usize| match line_offsets.binary_search(&byte) {
+        Ok(i) => i.min(lines.len().saturating_sub(1)),
+        Err(i) => i - 1,
+    };
+
+    let mut sorted: Vec<&Edit> = edits.iter().collect();
+    sorted.sort_by_key(|e| (e.byte_start, e.byte_end));
+    let mut pos = 0;
+    for e in &sorted {
+        check_fits(source, e, pos)?;
+        pos = e.byte_end;
+    }
+
+    // Group edits into line ranges, merging ranges that touch
+    let mut groups: Vec<(usize, usize, Vec<Edit>)> = Vec::new();
+    for e in sorted {
+        let start = line_of(e.byte_start);
+        // An empty source has no line for an insertion to land on
+        let end = (line_of(e.byte_end.saturating_sub(1).max(e.byte_start)) + 1).min(lines.len());
+        match groups.last_mut() {
+            Some((_, g_end, g_edits)) if start <= *g_end => {
+                *g_end = (*g_end).max(end);
+                g_edits.push(e.clone());
+            }
+            _ => groups.push((start, end, vec![e.clone()])),
+        }
+    }
+
+    let mut changes = Vec::new();
+    for (start, end, group) in groups {
+        let base = line_offsets[start];
+        let region = &source[base..line_offsets[end]];
+        let shifted: Vec<Edit> = group
+            .into_iter()
+            .map(|e| Edit {
+                byte_start: e.byte_start - base,
+                byte_end: e.byte_end - base,
+                ..e
+            })
+            .collect();
+        let replaced = apply_edits(region, &shifted)?;
+        changes.push(LineChange {
+            start,
+            end,
+            new_lines: replaced.split_inclusive('\n').map(str::to_string).collect(),
+        });
+    }
+
+    let mut out = format!("--- a/{}\n+++ b/{}\n", path, path);
+    let mut delta: isize = 0;
+    let mut i = 0;
+    while i < changes.len() {
+        // Extend the hunk while the next change is within two context windows
+        let mut j = i;
+        while j + 1 < changes.len() && changes[j + 1].start <= changes[j].end + 2 * DIFF_CONTEXT {
+            j += 1;
+        }
+        let hunk_start = changes[i].start.saturating_sub(DIFF_CONTEXT);
+        let hunk_end = (changes[j].end + DIFF_CONTEXT).min(lines.len());
+
+        let mut body = String::new();
+        let (mut old_len, mut new_len) = (0, 0);
+        let mut cursor = hunk_start;
+        for c in &changes[i..=j] {
+            for l in &lines[cursor..c.start] {
+                push_line(&mut body, ' ', l);
+            }
+            for l in &lines[c.start..c.end] {
+                push_line(&mut body, '-', l);
+            }
+            for l in &c.new_lines {
+                push_line(&mut body, '+', l);
+            }
+            old_len += c.start - cursor + (c.end - c.start);
+            new_len += c.start - cursor + c.new_lines.len();
+            cursor = c.end;
+        }
+        for l in &lines[cursor..hunk_end] {
+            push_line(&mut body, ' ', l);
+        }
+        old_len += hunk_end - cursor;
+        new_len += hunk_end - cursor;
+
+        let new_start = (hunk_start as isize + delta) as usize;
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(hunk_start, old_len),
+            hunk_range(new_start, new_len)
+        ));
+        out.push_str(&body);
+        delta += new_len as isize - old_len as isize;
+        i = j + 1;
+    }
+    Ok(out)
+}
+
+/// A hunk header range: 1-based start and length, except that an empty range
+/// starts at the line before it, as in `diff -u`.
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+fn push_line(out: &mut String, prefix: char, line: &str) {
+    out.push(prefix);
+    out.push_str(line.strip_suffix('\n').unwrap_or(line));
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(start: usize, end: usize, replacement: &str) -> Edit {
+        Edit {
+            file: "src/lib.rs".to_string(),
+            byte_start: start,
+            byte_end: end,
+            line_start: 1,
+            replacement: replacement.to_string(),
+        }
+    }
+
+    fn suggestion(applicability: Applicability, edits: Vec<Edit>) -> Suggestion {
+        Suggestion {
+            message: String::new(),
+            applicability,
+            edits,
+        }
+    }
+
+    fn diagnostic_with(suggestions: Vec<Suggestion>) -> Diagnostic {
+        let mut d = crate::fixture_diagnostics().remove(0);
+        d.suggestions = suggestions;
+        d
+    }
+
+    #[test]
+    fn test_apply_edits_in_order() {
+        let src = "let x = 1;\nlet y = 2;\n";
+        let out = apply_edits(src, &[edit(15, 16, "_y"), edit(4, 5, "_x")]).unwrap();
+        assert_eq!(out, "let _x = 1;\nlet _y = 2;\n");
+    }
+
+    #[test]
+    fn test_apply_edits_rejects_out_of_bounds() {
+        assert!(apply_edits("short", &[edit(3, 40, "")]).is_err());
+    }
+
+    #[test]
+    fn test_unified_diff_rejects_out_of_bounds() {
+        let err = unified_diff("src/lib.rs", "short\n", &[edit(3, 40, "")]).unwrap_err();
+        assert!(err.starts_with("edit 3..40 does not fit"), "{}", err);
+        let overlapping = [edit(0, 4, "a"), edit(2, 5, "b")];
+        assert!(unified_diff("src/lib.rs", "short\n", &overlapping).is_err());
+    }
+
+    #[test]
+    fn test_fix_plan_only_machine_applicable_and_sorted() {
+        let diags = crate::fixture_diagnostics();
+        let plan = select_fixes(&diags, false).fix_plan();
+        assert!(!plan.is_empty());
+        assert!(plan
+            .iter()
+            .all(|e| e["applicability"] == "MachineApplicable"));
+        let key = |e: &Value| (e["file"].to_string(), e["byte_start"].as_u64());
+        assert!(plan.windows(2).all(|w| key(&w[0]) <= key(&w[1])));
+
+        let mut repeated = diags.clone();
+        repeated.extend(crate::fixture_diagnostics());
+        assert_eq!(select_fixes(&repeated, false).fix_plan(), plan);
+    }
+
+    #[test]
+    fn test_select_fixes_skips_overlaps_and_duplicates() {
+        let d = diagnostic_with(vec![
+            suggestion(Applicability::MachineApplicable, vec![edit(0, 10, "")]),
+            suggestion(Applicability::MachineApplicable, vec![edit(0, 10, "")]),
+            suggestion(Applicability::MachineApplicable, vec![edit(5, 15, "x")]),
+            suggestion(Applicability::MaybeIncorrect, vec![edit(20, 25, "y")]),
+        ]);
+        let sel = select_fixes(std::slice::from_ref(&d), false);
+        assert_eq!(sel.accepted.len(), 1);
+        assert_eq!(sel.conflicts.len(), 1);
+
+        let sel = select_fixes(&[d], true);
+        assert_eq!(sel.accepted.len(), 2);
+    }
+
+    #[test]
+    fn test_unified_diff_hunks() {
+        let src = (1..=20)
+            .map(|i| format!("line {}\n", i))
+            .collect::<String>();
+        // Replace "line 2" and delete the whole of line 18
+        let l2 = src.find("line 2\n").unwrap();
+        let l18 = src.find("line 18\n").unwrap();
+        let diff = unified_diff(
+            "src/lib.rs",
+            &src,
+            &[edit(l2, l2 + 6, "LINE 2"), edit(l18, l18 + 8, "")],
+        )
+        .unwrap();
+        assert!(diff.starts_with("--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,5 +1,5 @@\n"));
+        assert!(diff.contains("-line 2\n+LINE 2\n"));
+        assert!(diff.contains("@@ -15,6 +15,5 @@\n line 15\n"));
+        assert!(diff.contains("-line 18\n line 19\n"));
+    }
+
+    #[test]
+    fn test_unified_diff_empty_ranges() {
+        let diff = unified_diff("src/lib.rs", "", &[edit(0, 0, "fn f() {}\n")]).unwrap();
+        assert!(diff.ends_with("@@ -0,0 +1,1 @@\n+fn f() {}\n"), "{}", diff);
+
+        let diff = unified_diff("src/lib.rs", "a\nb\n", &[edit(0, 4, "")]).unwrap();
+        assert!(diff.ends_with("@@ -1,2 +0,0 @@\n-a\n-b\n"), "{}", diff);
+    }
+
+    #[test]
+    fn test_unified_diff_insertion_at_eof_without_newline() {
+        let diff = unified_diff("src/lib.rs", "a\nb", &[edit(3, 3, "\nc")]).unwrap();
+        assert!(
+            diff.ends_with("@@ -1,2 +1,3 @@\n a\n-b\n+b\n+c\n"),
+            "{}",
+            diff
+        );
+    }
+}
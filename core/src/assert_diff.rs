@@ -0,0 +1,337 @@
+/// Operands shorter than this are shown in full; a diff only pays off for big values.
+pub const MIN_DIFF_CHARS: usize = 80;
+
+/// Unchanged characters kept around a change in a string diff, per line of context.
+pub const STRING_CONTEXT_CHARS: usize = 10;
+
+/// Largest LCS table worth filling; beyond it the differing middle is shown
+/// as one replacement.
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal(usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Minimal edit script turning `a` into `b`: the common prefix and suffix are
+/// trimmed first, then the middle is diffed through a longest common subsequence.
+fn diff_ops<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Op> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut ops = vec![Op::Equal(prefix)];
+    let (n, m) = (a_mid.len(), b_mid.len());
+    if n * m > MAX_LCS_CELLS {
+        ops.extend([Op::Delete(n), Op::Insert(m)]);
+    } else {
+        // lcs[i][j]: length of the LCS of a_mid[i..] and b_mid[j..]
+        let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if a_mid[i] == b_mid[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && a_mid[i] == b_mid[j] {
+                ops.push(Op::Equal(1));
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                ops.push(Op::Delete(1));
+                i += 1;
+            } else {
+                ops.push(Op::Insert(1));
+                j += 1;
+            }
+        }
+    }
+    ops.push(Op::Equal(suffix));
+
+    // Merge runs, dropping empty ones
+    let mut merged: Vec<Op> = Vec::new();
+    for op in ops {
+        match (merged.last_mut(), op) {
+            (_, Op::Equal(0) | Op::Delete(0) | Op::Insert(0)) => {}
+            (Some(Op::Equal(n)), Op::Equal(k))
+            | (Some(Op::Delete(n)), Op::Delete(k))
+            | (Some(Op::Insert(n)), Op::Insert(k)) => *n += k,
+            _ => merged.push(op),
+        }
+    }
+    merged
+}
+
+/// The contents of a `{:?}`-printed string, still escaped.
+fn string_literal(value: &str) -> Option<&str> {
+    value
+        .strip_prefix('"')?
+        .strip_suffix('"')
+        .filter(|inner| !inner.ends_with('\\') || inner.ends_with("\\\\"))
+}
+
+/// Breaks single-line `{:?}` output into `{:#?}`-like lines, one field or
+/// element per line, so big structs and collections can be diffed by line.
+fn pretty_debug(value: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut depth = 0usize;
+    let mut chars = value.chars().peekable();
+    let newline = |lines: &mut Vec<String>, line: &mut String, depth: usize| {
+        let trimmed = line.trim_end();
+        if !trimmed.trim_start().is_empty() {
+            lines.push(trimmed.to_string());
+        }
+        *line = "    ".repeat(depth);
+    };
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                // Copy the string literal as is, escapes included
+                line.push(c);
+                while let Some(c) = chars.next() {
+                    line.push(c);
+                    match c {
+                        '\\' => line.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '\'' => {
+                // A char literal such as '{' or '\''
+                line.push(c);
+                if let Some(c) = chars.next() {
+                    line.push(c);
+                    if c == '\\' {
+                        line.extend(chars.next());
+                    }
+                }
+                if chars.peek() == Some(&'\'') {
+                    line.extend(chars.next());
+                }
+            }
+            '{' | '[' | '(' => {
+                line.push(c);
+                let close = match c {
+                    '{' => '}',
+                    '[' => ']',
+                    _ => ')',
+                };
+                if chars.peek() == Some(&close) {
+                    line.extend(chars.next());
+                } else {
+                    depth += 1;
+                    newline(&mut lines, &mut line, depth);
+                    while chars.peek() == Some(&' ') {
+                        chars.next();
+                    }
+                }
+            }
+            '}' | ']' | ')' => {
+                depth = depth.saturating_sub(1);
+                newline(&mut lines, &mut line, depth);
+                line.push(c);
+            }
+            ',' => {
+                line.push(c);
+                newline(&mut lines, &mut line, depth);
+                while chars.peek() == Some(&' ') {
+                    chars.next();
+                }
+            }
+            _ => line.push(c),
+        }
+    }
+    newline(&mut lines, &mut line, 0);
+    lines
+}
+
+/// Character-level diff of two strings: changes as `[-left-]{+right+}`, with
+/// `context` unchanged characters either side and `…` for what's skipped.
+fn char_diff(left: &str, right: &str, context: usize) -> String {
+    let (a, b): (Vec<char>, Vec<char>) = (left.chars().collect(), right.chars().collect());
+    let ops = diff_ops(&a, &b);
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    for (k, op) in ops.iter().enumerate() {
+        match *op {
+            Op::Equal(n) => {
+                let run = &a[i..i + n];
+                let (first, last) = (k == 0, k == ops.len() - 1);
+                if first && n > context {
+                    out.push('…');
+                    out.extend(&run[n - context..]);
+                } else if last && n > context {
+                    out.extend(&run[..context]);
+                    out.push('…');
+                } else if !first && !last && n > 2 * context {
+                    out.extend(&run[..context]);
+                    out.push('…');
+                    out.extend(&run[n - context..]);
+                } else {
+                    out.extend(run);
+                }
+                i += n;
+                j += n;
+            }
+            Op::Delete(n) => {
+                out.push_str("[-");
+                out.extend(&a[i..i + n]);
+                out.push_str("-]");
+                i += n;
+            }
+            Op::Insert(n) => {
+                out.push_str("{+");
+                out.extend(&b[j..j + n]);
+                out.push_str("+}");
+                j += n;
+            }
+        }
+    }
+    out
+}
+
+/// Line-level diff: `- ` left-only and `+ ` right-only lines, `context`
+/// unchanged lines around each change and a `⋮` marker for skipped ones.
+fn line_diff(left: &[String], right: &[String], context: usize) -> String {
+    let ops = diff_ops(left, right);
+    let mut out: Vec<String> = Vec::new();
+    let skipped = |n: usize| format!("  ⋮ {} unchanged line{}", n, if n == 1 { "" } else { "s" });
+    let (mut i, mut j) = (0, 0);
+    for (k, op) in ops.iter().enumerate() {
+        match *op {
+            Op::Equal(n) => {
+                let run = &left[i..i + n];
+                let (first, last) = (k == 0, k == ops.len() - 1);
+                let (head, tail) = match (first, last) {
+                    (true, _) => (0, context),
+                    (_, true) => (context, 0),
+                    _ => (context, context),
+                };
+                if n > head + tail {
+                    out.extend(run[..head].iter().map(|l| format!("  {}", l)));
+                    out.push(skipped(n - head - tail));
+                    out.extend(run[n - tail..].iter().map(|l| format!("  {}", l)));
+                } else {
+                    out.extend(run.iter().map(|l| format!("  {}", l)));
+                }
+                i += n;
+                j += n;
+            }
+            Op::Delete(n) => {
+                out.extend(left[i..i + n].iter().map(|l| format!("- {}", l)));
+                i += n;
+            }
+            Op::Insert(n) => {
+                out.extend(right[j..j + n].iter().map(|l| format!("+ {}", l)));
+                j += n;
+            }
+        }
+    }
+    out.join("\n")
+}
+
+/// A compact diff of the `left`/`right` operands of a failed `assert_eq!`, or
+/// `None` when both are short enough to read in full. Strings are diffed by
+/// character (`context` × [`STRING_CONTEXT_CHARS`] characters of context);
+/// anything else by line, after breaking single-line `{:?}` output into
+/// `{:#?}`-like lines, with `context` unchanged lines around each change.
+pub fn assert_diff(left: &str, right: &str, context: usize) -> Option<String> {
+    if left.chars().count().max(right.chars().count()) < MIN_DIFF_CHARS || left == right {
+        return None;
+    }
+    if let (Some(l), Some(r)) = (string_literal(left), string_literal(right)) {
+        return Some(format!(
+            "\"{}\"",
+            char_diff(l, r, context * STRING_CONTEXT_CHARS)
+        ));
+    }
+    let lines = |value: &str| match value.contains('\n') {
+        true => value.lines().map(str::to_string).collect::<Vec<_>>(),
+        false => pretty_debug(value),
+    };
+    Some(line_diff(&lines(left), &lines(right), context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_operands_are_not_diffed() {
+        assert_eq!(assert_diff("4", "5", 3), None);
+    }
+
+    #[test]
+    fn test_string_diff_is_char_level_with_context() {
+        let base = "the quick brown fox jumps over the lazy dog; ".repeat(3);
+        let left = format!("{:?}", format!("{}fox{}", base, base));
+        let right = format!("{:?}", format!("{}cat{}", base, base));
+        let diff = assert_diff(&left, &right, 1).unwrap();
+        assert_eq!(diff, "\"…lazy dog; [-fox-]{+cat+}the quick …\"");
+    }
+
+    #[test]
+    fn test_struct_diff_is_line_level() {
+        let fields = |b: i32| {
+            format!(
+                "Config {{ name: \"server\", port: 8080, hosts: [\"a.example\", \"b.example\"], \
+                 retries: {}, timeout: Some(30), tags: [] }}",
+                b
+            )
+        };
+        let diff = assert_diff(&fields(3), &fields(4), 1).unwrap();
+        assert_eq!(
+            diff,
+            "  ⋮ 6 unchanged lines\n      ],\n-     retries: 3,\n+     retries: 4,\n      timeout: Some(\n  ⋮ 4 unchanged lines"
+        );
+    }
+
+    #[test]
+    fn test_pretty_debug_keeps_literals_intact() {
+        let lines = pretty_debug("Foo { a: \"x, {y}\", b: '{', c: [] }");
+        assert_eq!(
+            lines,
+            vec![
+                "Foo {",
+                "    a: \"x, {y}\",",
+                "    b: '{',",
+                "    c: []",
+                "}"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multiline_values_diff_by_line() {
+        let left: String = (0..30).map(|i| format!("line {}\n", i)).collect();
+        let right = left.replace("line 15\n", "line fifteen\n");
+        let diff = assert_diff(&left, &right, 0).unwrap();
+        assert_eq!(
+            diff,
+            "  ⋮ 15 unchanged lines\n- line 15\n+ line fifteen\n  ⋮ 14 unchanged lines"
+        );
+    }
+
+    #[test]
+    fn test_diff_ops_minimal() {
+        let ops = diff_ops(&['a', 'b', 'c', 'd'], &['a', 'c', 'd', 'e']);
+        assert_eq!(
+            ops,
+            vec![Op::Equal(1), Op::Delete(1), Op::Equal(2), Op::Insert(1)]
+        );
+    }
+}
@@ -0,0 +1,350 @@
+use crate::{item_fingerprint, normalize_message, normalize_path, Diagnostic};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// Version of the baseline file format written by [`Baseline::to_json`].
+const BASELINE_VERSION: u64 = 1;
+
+/// Recognizes an item header such as `pub(crate) async fn foo(` or
+/// `impl<T> Trait for Foo<T> {` and returns a short label: `fn foo`, `impl<T> Trait for Foo<T>`.
+fn item_header(line: &str) -> Option<String> {
+    let mut rest = line.trim_start();
+    if let Some(after) = rest.strip_prefix("pub") {
+        rest = match after.strip_prefix('(') {
+            Some(scope) => scope.split_once(')')?.1,
+            None => after,
+        }
+        .trim_start();
+    }
+    let mut words = rest.split_whitespace().peekable();
+    while let Some(w) = words.peek() {
+        if matches!(*w, "async" | "unsafe" | "default" | "const" | "extern") || w.starts_with('"') {
+            words.next();
+        } else {
+            break;
+        }
+    }
+    let keyword = words.next()?;
+    if keyword == "impl" || keyword.starts_with("impl<") {
+        let start = rest.find(keyword)?;
+        let header = rest[start..].split(['{', ';']).next()?;
+        let header = header.split(" where").next()?;
+        return Some(header.split_whitespace().collect::<Vec<_>>().join(" "));
+    }
+    let kind = match keyword {
+        "fn" | "struct" | "enum" | "trait" | "mod" | "union" | "macro_rules!" => keyword,
+        _ => return None,
+    };
+    let name: String = words
+        .next()?
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    (!name.is_empty()).then(|| format!("{} {}", kind, name))
+}
+
+/// Names the items enclosing 1-based `line` of `source`, outermost first, e.g.
+/// `impl Parser > fn parse`; empty at file level. Works from indentation, so it
+/// only needs the source text, not a parse.
+pub fn enclosing_item(source: &str, line: usize) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    if line == 0 || line > lines.len() {
+        return String::new();
+    }
+    let indent = |l: &str| l.len() - l.trim_start().len();
+
+    let mut path = Vec::new();
+    let mut limit = indent(lines[line - 1]);
+    if let Some(header) = item_header(lines[line - 1]) {
+        path.push(header);
+    }
+    for l in lines[..line - 1].iter().rev() {
+        if limit == 0 {
+            break;
+        }
+        // Braces, attributes, comments and where clauses sit at the item's own
+        // indentation without opening a scope
+        let trimmed = l.trim_start();
+        let opens_scope = trimmed.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && trimmed != "where"
+            && !trimmed.starts_with("where ");
+        if !opens_scope {
+            continue;
+        }
+        let ind = indent(l);
+        if ind < limit {
+            limit = ind;
+            if let Some(header) = item_header(l) {
+                path.push(header);
+            }
+        }
+    }
+    path.reverse();
+    path.join(" > ")
+}
+
+/// [`enclosing_item`] for each diagnostic's primary span. `read` loads a source
+/// file by the path rustc reported; unreadable files count as file level.
+pub fn enclosing_items<F>(diagnostics: &[Diagnostic], mut read: F) -> Vec<String>
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut cache: HashMap<String, Option<String>> = HashMap::new();
+    diagnostics
+        .iter()
+        .map(|d| {
+            let source = cache
+                .entry(d.span.file.clone())
+                .or_insert_with(|| read(&d.span.file));
+            source
+                .as_deref()
+                .map(|s| enclosing_item(s, d.span.line_start))
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// A known issue: what it was, and how many times it occurred.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BaselineEntry {
+    pub code: Option<String>,
+    pub file: String,
+    pub item: String,
+    pub message: String,
+    pub count: usize,
+}
+
+/// Known diagnostics keyed by [`item_fingerprint`], so they stay matched when
+/// lines shift but not when the same issue appears in another item.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Baseline {
+    pub entries: BTreeMap<String, BaselineEntry>,
+}
+
+impl Baseline {
+    /// Records `diagnostics`; `items` are their [`enclosing_items`].
+    pub fn from_diagnostics(diagnostics: &[Diagnostic], items: &[String]) -> Self {
+        let mut baseline = Baseline::default();
+        for (d, item) in diagnostics.iter().zip(items) {
+            baseline
+                .entries
+                .entry(item_fingerprint(d, item))
+                .and_modify(|e| e.count += 1)
+                .or_insert_with(|| BaselineEntry {
+                    code: d.code.clone(),
+                    file: normalize_path(&d.span.file).to_string(),
+                    item: item.clone(),
+                    message: normalize_message(&d.message),
+                    count: 1,
+                });
+        }
+        baseline
+    }
+
+    /// Total number of diagnostics recorded.
+    pub fn len(&self) -> usize {
+        self.entries.values().map(|e| e.count).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// File form, sorted by file then fingerprint so it diffs well under version control.
+    pub fn to_json(&self) -> Value {
+        let mut entries: Vec<(&String, &BaselineEntry)> = self.entries.iter().collect();
+        entries.sort_by(|a, b| (&a.1.file, a.0).cmp(&(&b.1.file, b.0)));
+        json!({
+            "version": BASELINE_VERSION,
+            "entries": entries
+                .into_iter()
+                .map(|(fingerprint, e)| json!({
+                    "fingerprint": fingerprint,
+                    "code": e.code,
+                    "file": e.file,
+                    "item": e.item,
+                    "message": e.message,
+                    "count": e.count,
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    pub fn from_json(v: &Value) -> Result<Self, String> {
+        match v["version"].as_u64() {
+            Some(BASELINE_VERSION) => {}
+            Some(other) => return Err(format!("unsupported baseline version {}", other)),
+            None => return Err("not a cargo-chec baseline (missing \"version\")".to_string()),
+        }
+        let mut baseline = Baseline::default();
+        for e in v["entries"].as_array().ok_or("missing \"entries\" array")? {
+            let fingerprint = e["fingerprint"]
+                .as_str()
+                .ok_or("baseline entry without \"fingerprint\"")?;
+            baseline.entries.insert(
+                fingerprint.to_string(),
+                BaselineEntry {
+                    code: e["code"].as_str().map(str::to_string),
+                    file: e["file"].as_str().unwrap_or("").to_string(),
+                    item: e["item"].as_str().unwrap_or("").to_string(),
+                    message: e["message"].as_str().unwrap_or("").to_string(),
+                    count: e["count"].as_u64().unwrap_or(1) as usize,
+                },
+            );
+        }
+        Ok(baseline)
+    }
+
+    /// Splits off the diagnostics not covered by the baseline. Each entry covers
+    /// up to `count` matching diagnostics; any more than that are new.
+    /// Returns the new diagnostics and the number suppressed.
+    pub fn new_diagnostics(
+        &self,
+        diagnostics: Vec<Diagnostic>,
+        items: &[String],
+    ) -> (Vec<Diagnostic>, usize) {
+        let mut remaining: HashMap<&str, usize> = self
+            .entries
+            .iter()
+            .map(|(fingerprint, e)| (fingerprint.as_str(), e.count))
+            .collect();
+        let mut suppressed = 0;
+        let new = diagnostics
+            .into_iter()
+            .zip(items)
+            .filter(
+                |(d, item)| match remaining.get_mut(item_fingerprint(d, item).as_str()) {
+                    Some(n) if *n > 0 => {
+                        *n -= 1;
+                        suppressed += 1;
+                        false
+                    }
+                    _ => true,
+                },
+            )
+            .map(|(d, _)| d)
+            .collect();
+        (new, suppressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture_diagnostics;
+    use std::fs;
+
+    const SRC: &str = "\
+use std::fmt;
+
+pub(crate) struct Parser {
+    pos: usize,
+}
+
+impl<T> fmt::Display for Wrapper<T>
+where
+    T: Clone,
+{
+    pub async fn parse(&self) {
+        let x = foo(
+            bar,
+        );
+    }
+}
+
+fn other() {}
+";
+
+    #[test]
+    fn test_enclosing_item_paths() {
+        assert_eq!(enclosing_item(SRC, 1), "");
+        assert_eq!(enclosing_item(SRC, 4), "struct Parser");
+        assert_eq!(
+            enclosing_item(SRC, 13),
+            "impl<T> fmt::Display for Wrapper<T> > fn parse"
+        );
+        assert_eq!(
+            enclosing_item(SRC, 11),
+            "impl<T> fmt::Display for Wrapper<T> > fn parse"
+        );
+        assert_eq!(enclosing_item(SRC, 18), "fn other");
+        assert_eq!(enclosing_item(SRC, 99), "");
+    }
+
+    #[test]
+    fn test_item_header_forms() {
+        assert_eq!(
+            item_header("    pub(in crate::a) const unsafe fn f<T>()"),
+            Some("fn f".into())
+        );
+        assert_eq!(item_header("extern \"C\" fn cb()"), Some("fn cb".into()));
+        assert_eq!(
+            item_header("macro_rules! my_macro {"),
+            Some("macro_rules! my_macro".into())
+        );
+        assert_eq!(item_header("    let fn_ptr = f;"), None);
+        assert_eq!(item_header("const MAX: usize = 3;"), None);
+    }
+
+    fn fixture() -> (Vec<Diagnostic>, Vec<String>) {
+        let diags = fixture_diagnostics();
+        let source = fs::read_to_string("../tools/broken-tests/src/lib.rs").unwrap();
+        let items = enclosing_items(&diags, |f| (f == "src/lib.rs").then(|| source.clone()));
+        (diags, items)
+    }
+
+    #[test]
+    fn test_fixture_items() {
+        let (diags, items) = fixture();
+        let i = diags
+            .iter()
+            .position(|d| d.message.contains("completely_undefined_variable"))
+            .unwrap();
+        assert_eq!(items[i], "fn unresolved_variable");
+    }
+
+    #[test]
+    fn test_baseline_suppresses_known_and_survives_line_shift() {
+        let (diags, items) = fixture();
+        let baseline = Baseline::from_diagnostics(&diags, &items);
+        assert_eq!(baseline.len(), diags.len());
+
+        let round_trip = Baseline::from_json(&baseline.to_json()).unwrap();
+        assert_eq!(round_trip, baseline);
+
+        let mut shifted = diags.clone();
+        for d in &mut shifted {
+            d.span.line_start += 3;
+            d.span.line_end += 3;
+        }
+        let (new, suppressed) = round_trip.new_diagnostics(shifted, &items);
+        assert!(new.is_empty());
+        assert_eq!(suppressed, diags.len());
+    }
+
+    #[test]
+    fn test_baseline_reports_extra_occurrences_and_new_items() {
+        let (diags, items) = fixture();
+        let baseline = Baseline::from_diagnostics(&diags, &items);
+
+        let mut more = diags.clone();
+        more.push(diags[0].clone());
+        let mut more_items = items.clone();
+        more_items.push(items[0].clone());
+        let mut moved = diags[1].clone();
+        moved.message = "a brand new problem".to_string();
+        more.push(moved);
+        more_items.push(items[1].clone());
+
+        let (new, suppressed) = baseline.new_diagnostics(more, &more_items);
+        assert_eq!(suppressed, diags.len());
+        assert_eq!(new.len(), 2);
+        assert_eq!(new[1].message, "a brand new problem");
+    }
+
+    #[test]
+    fn test_from_json_rejects_other_files() {
+        assert!(Baseline::from_json(&json!([])).is_err());
+        assert!(Baseline::from_json(&json!({ "version": 99, "entries": [] })).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Rough characters-per-token ratio used to turn `--max-tokens` into a character budget.
+pub const CHARS_PER_TOKEN: usize = 4;
+
+/// Smallest remaining space worth filling with a truncated string item.
+const MIN_TRUNCATED_CHARS: usize = 64;
+
+/// Combines `--max-chars` and `--max-tokens` into one character budget (the tighter wins).
+pub fn char_budget(max_chars: Option<usize>, max_tokens: Option<usize>) -> Option<usize> {
+    let from_tokens = max_tokens.map(|t| t.saturating_mul(CHARS_PER_TOKEN));
+    match (max_chars, from_tokens) {
+        (Some(c), Some(t)) => Some(c.min(t)),
+        (c, t) => c.or(t),
+    }
+}
+
+/// Items dropped to fit a budget, counted per category.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Omitted(pub BTreeMap<String, usize>);
+
+impl Omitted {
+    pub fn total(&self) -> usize {
+        self.0.values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total() == 0
+    }
+
+    /// Marker object for structured outputs: `{"omitted": 12, "by_category": {...}}`.
+    pub fn to_json(&self) -> Value {
+        json!({ "omitted": self.total(), "by_category": self.0 })
+    }
+}
+
+/// Marker string for string-array outputs: `12 more omitted (error: 2, warning: 10)`.
+impl fmt::Display for Omitted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .0
+            .iter()
+            .map(|(category, n)| format!("{}: {}", category, n))
+            .collect();
+        write!(f, "{} more omitted ({})", self.total(), parts.join(", "))
+    }
+}
+
+fn json_len(v: &Value) -> usize {
+    serde_json::to_string(v).map(|s| s.len()).unwrap_or(0)
+}
+
+/// Keeps as many `(category, item)` pairs as fit in `max_chars` of compact JSON
+/// array output, in the given (priority) order, reserving room for the omitted
+/// marker. When the next item doesn't fit, a string item is cut short to fill
+/// the remaining space, and everything after it is counted as omitted.
+pub fn fit_to_budget(items: Vec<(String, Value)>, max_chars: usize) -> (Vec<Value>, Omitted) {
+    let total: usize = 2 + items.iter().map(|(_, v)| json_len(v) + 1).sum::<usize>();
+    if total <= max_chars {
+        return (
+            items.into_iter().map(|(_, v)| v).collect(),
+            Omitted::default(),
+        );
+    }
+
+    // Worst case marker: every item omitted
+    let mut worst = Omitted::default();
+    for (category, _) in &items {
+        *worst.0.entry(category.clone()).or_default() += 1;
+    }
+    let reserve = json_len(&json!(worst.to_string())).max(json_len(&worst.to_json())) + 1;
+    let mut remaining = max_chars.saturating_sub(2 + reserve);
+
+    let mut kept = Vec::new();
+    let mut omitted = Omitted::default();
+    let mut full = false;
+    for (category, item) in items {
+        let cost = json_len(&item) + 1;
+        if !full && cost <= remaining {
+            remaining -= cost;
+            kept.push(item);
+            continue;
+        }
+        if !full {
+            full = true;
+            if let Some(s) = item.as_str().filter(|_| remaining >= MIN_TRUNCATED_CHARS) {
+                kept.push(json!(truncate_str(s, remaining)));
+                continue;
+            }
+        }
+        *omitted.0.entry(category).or_default() += 1;
+    }
+    (kept, omitted)
+}
+
+/// Shortens `s` so that its JSON encoding plus a separator fits in `max_chars`, ending in "…".
+fn truncate_str(s: &str, max_chars: usize) -> String {
+    let mut end = s.len();
+    loop {
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        let candidate = format!("{}…", &s[..end]);
+        let cost = json_len(&json!(candidate)) + 1;
+        if end == 0 || cost <= max_chars {
+            return candidate;
+        }
+        end = end.saturating_sub(cost - max_chars);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(n: usize, category: &str, len: usize) -> Vec<(String, Value)> {
+        (0..n)
+            .map(|i| {
+                (
+                    category.to_string(),
+                    json!(format!("{}{}", i, "x".repeat(len))),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_char_budget_takes_tighter_limit() {
+        assert_eq!(char_budget(None, None), None);
+        assert_eq!(char_budget(Some(500), None), Some(500));
+        assert_eq!(char_budget(None, Some(100)), Some(400));
+        assert_eq!(char_budget(Some(300), Some(100)), Some(300));
+    }
+
+    #[test]
+    fn test_fits_everything_under_budget() {
+        let (kept, omitted) = fit_to_budget(items(3, "error", 10), 10_000);
+        assert_eq!(kept.len(), 3);
+        assert!(omitted.is_empty());
+    }
+
+    #[test]
+    fn test_truncates_in_priority_order_and_counts_categories() {
+        let mut all = items(5, "error", 100);
+        all.extend(items(20, "warning", 100));
+        let (mut kept, omitted) = fit_to_budget(all, 1000);
+        assert!(kept[0].as_str().unwrap().starts_with('0'));
+        assert_eq!(kept.len() + omitted.total(), 25);
+        assert!(!omitted.0.contains_key("error"));
+        let dropped = omitted.0["warning"];
+        assert!(dropped > 10);
+        assert_eq!(
+            omitted.to_string(),
+            format!("{} more omitted (warning: {})", dropped, dropped)
+        );
+
+        kept.push(json!(omitted.to_string()));
+        assert!(serde_json::to_string(&kept).unwrap().len() <= 1000);
+    }
+
+    #[test]
+    fn test_oversized_first_item_is_cut_short() {
+        let (kept, omitted) = fit_to_budget(items(2, "error", 5000), 400);
+        assert_eq!(kept.len(), 1);
+        assert!(kept[0].as_str().unwrap().ends_with('…'));
+        assert_eq!(omitted.total(), 1);
+    }
+}
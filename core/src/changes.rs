@@ -0,0 +1,208 @@
+use crate::{Diagnostic, Span};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Lines added or modified since a git ref, per absolute file path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangedLines {
+    files: HashMap<PathBuf, Vec<(usize, usize)>>,
+}
+
+/// Parses a `+c,d` hunk range into the inclusive new-side line range. Pure
+/// deletions (`d == 0`) mark the line the removed text sat next to.
+fn new_range(spec: &str) -> Option<(usize, usize)> {
+    let spec = spec.strip_prefix('+')?;
+    let (start, len) = match spec.split_once(',') {
+        Some((s, l)) => (s.parse::<usize>().ok()?, l.parse::<usize>().ok()?),
+        None => (spec.parse().ok()?, 1),
+    };
+    Some(match len {
+        0 => (start.max(1), start.max(1)),
+        n => (start, start + n - 1),
+    })
+}
+
+impl ChangedLines {
+    /// Parses `git diff --unified=0` output; paths are relative to `repo_root`.
+    pub fn from_diff(diff: &str, repo_root: &Path) -> Self {
+        let mut changed = ChangedLines::default();
+        let mut current: Option<PathBuf> = None;
+        for line in diff.lines() {
+            if let Some(path) = line.strip_prefix("+++ ") {
+                let path = path.trim_matches('"');
+                current = path.strip_prefix("b/").map(|p| repo_root.join(p));
+                if let Some(p) = &current {
+                    changed.files.entry(p.clone()).or_default();
+                }
+            } else if let Some(hunk) = line.strip_prefix("@@ ") {
+                let range = hunk.split_whitespace().nth(1).and_then(new_range);
+                if let (Some(file), Some(range)) = (&current, range) {
+                    changed.files.entry(file.clone()).or_default().push(range);
+                }
+            }
+        }
+        changed
+    }
+
+    /// Marks every line of `path` as changed, e.g. for a new untracked file.
+    pub fn add_whole_file(&mut self, path: PathBuf) {
+        self.files.insert(path, vec![(1, usize::MAX)]);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn contains_file(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    /// Whether lines `start..=end` of `path` overlap a changed range.
+    pub fn touches(&self, path: &Path, start: usize, end: usize) -> bool {
+        self.files
+            .get(path)
+            .is_some_and(|ranges| ranges.iter().any(|(s, e)| *s <= end && start <= *e))
+    }
+
+    fn span_touches(&self, span: &Span, root: &Path, whole_files: bool) -> bool {
+        let path = root.join(&span.file);
+        if whole_files {
+            self.contains_file(&path)
+        } else {
+            self.touches(&path, span.line_start, span.line_end)
+        }
+    }
+
+    /// Whether the diagnostic's primary span or any related span falls on a
+    /// changed line (or, with `whole_files`, in a changed file). Relative paths
+    /// reported by rustc are resolved against the workspace `root`.
+    pub fn touches_diagnostic(&self, d: &Diagnostic, root: &Path, whole_files: bool) -> bool {
+        std::iter::once(&d.span)
+            .chain(d.related.iter().map(|r| &r.span))
+            .any(|span| self.span_touches(span, root, whole_files))
+    }
+}
+
+fn git(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|e| format!("failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    String::from_utf8(output.stdout).map_err(|e| e.to_string())
+}
+
+/// Lines changed in the working tree since `git_ref` (staged or not), plus
+/// untracked files in full, as reported by the git repository around the
+/// current directory.
+pub fn git_changed_lines(git_ref: &str) -> Result<ChangedLines, String> {
+    let root = PathBuf::from(git(&["rev-parse", "--show-toplevel"])?.trim_end());
+    let root = root.canonicalize().unwrap_or(root);
+    let diff = git(&[
+        "diff",
+        "--unified=0",
+        "--no-color",
+        "--no-ext-diff",
+        "--no-renames",
+        git_ref,
+        "--",
+    ])?;
+    let mut changed = ChangedLines::from_diff(&diff, &root);
+    for file in git(&["ls-files", "--others", "--exclude-standard", "--full-name"])?.lines() {
+        changed.add_whole_file(root.join(file));
+    }
+    Ok(changed)
+}
+
+/// The workspace root that rustc's relative paths are based on, from
+/// `cargo locate-project --workspace`.
+pub fn workspace_root() -> Result<PathBuf, String> {
+    let output = Command::new("cargo")
+        .args(["locate-project", "--workspace", "--message-format", "plain"])
+        .output()
+        .map_err(|e| format!("failed to run cargo locate-project: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let manifest = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim_end());
+    let root = manifest
+        .parent()
+        .ok_or("cargo locate-project returned no directory")?
+        .to_path_buf();
+    Ok(root.canonicalize().unwrap_or(root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture_diagnostics;
+
+    const DIFF: &str = "\
+diff --git a/tools/broken-tests/src/lib.rs b/tools/broken-tests/src/lib.rs
+index 1111111..2222222 100644
+--- a/tools/broken-tests/src/lib.rs
++++ b/tools/broken-tests/src/lib.rs
+@@ -79 +79 @@ pub fn type_mismatch() -> i32 {
+-    String::from(\"x\")
++    String::from(\"not an int\")
+@@ -130,0 +135,2 @@ fn helper() {
++    one();
++    two();
+@@ -200,3 +206,0 @@
+diff --git a/README.md b/README.md
+deleted file mode 100644
+--- a/README.md
++++ /dev/null
+@@ -1,3 +0,0 @@
+";
+
+    fn root() -> PathBuf {
+        PathBuf::from("/repo")
+    }
+
+    #[test]
+    fn test_from_diff_ranges() {
+        let changed = ChangedLines::from_diff(DIFF, &root());
+        let lib = root().join("tools/broken-tests/src/lib.rs");
+        assert!(changed.touches(&lib, 79, 79));
+        assert!(changed.touches(&lib, 130, 136));
+        assert!(!changed.touches(&lib, 137, 150));
+        assert!(changed.touches(&lib, 206, 206));
+        assert!(!changed.contains_file(&root().join("README.md")));
+    }
+
+    #[test]
+    fn test_touches_primary_or_related_span() {
+        let diags = fixture_diagnostics();
+        let changed = ChangedLines::from_diff(DIFF, &root());
+        let ws = root().join("tools/broken-tests");
+        let touching: Vec<&Diagnostic> = diags
+            .iter()
+            .filter(|d| changed.touches_diagnostic(d, &ws, false))
+            .collect();
+        // line 79: mismatched types; line 135: the function E0061 points at as related
+        assert_eq!(touching.len(), 2, "{:#?}", touching);
+        assert!(touching.iter().any(|d| d.span.line_start == 79));
+        assert!(touching.iter().any(|d| d.code.as_deref() == Some("E0061")));
+
+        let whole: usize = diags
+            .iter()
+            .filter(|d| changed.touches_diagnostic(d, &ws, true))
+            .count();
+        assert_eq!(whole, 33);
+    }
+
+    #[test]
+    fn test_whole_file_for_untracked() {
+        let mut changed = ChangedLines::default();
+        changed.add_whole_file(root().join("src/new.rs"));
+        assert!(changed.touches(&root().join("src/new.rs"), 5000, 5001));
+    }
+}
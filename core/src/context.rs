@@ -0,0 +1,127 @@
+use crate::{Diagnostic, Span};
+use std::collections::HashMap;
+
+/// Opening and closing marks around the highlighted columns of a snippet.
+const MARK_OPEN: char = '⟦';
+const MARK_CLOSE: char = '⟧';
+
+/// Renders `context` lines either side of `span` as `"<line>: <text>"`, with
+/// the span's columns wrapped in ⟦ ⟧ and common indentation removed.
+///
+/// Reads from `source` when available; otherwise falls back to the span's own
+/// lines as reported by rustc (no surrounding context).
+pub fn snippet(span: &Span, source: Option<&str>, context: usize) -> Vec<String> {
+    let (first, lines): (usize, Vec<&str>) = match source {
+        Some(src) => {
+            let first = span.line_start.saturating_sub(context).max(1);
+            let last = span.line_end + context;
+            let lines = src.lines().skip(first - 1).take(last + 1 - first).collect();
+            (first, lines)
+        }
+        None => (
+            span.line_start,
+            span.text.iter().map(String::as_str).collect(),
+        ),
+    };
+
+    let indent = lines
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.chars().take_while(|c| c.is_whitespace()).count())
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let number = first + i;
+            let chars: Vec<char> = line.chars().skip(indent).collect();
+            // Columns are 1-based; one inside the removed indentation marks the start
+            let at = |col: usize| col.saturating_sub(1 + indent).min(chars.len());
+            let mut text = String::new();
+            for pos in 0..=chars.len() {
+                if number == span.line_start && pos == at(span.col_start) {
+                    text.push(MARK_OPEN);
+                }
+                if number == span.line_end && pos == at(span.col_end) {
+                    text.push(MARK_CLOSE);
+                }
+                text.extend(chars.get(pos));
+            }
+            format!("{}: {}", number, text.trim_end())
+        })
+        .collect()
+}
+
+/// Fills `context` on each diagnostic with a [`snippet`] of its primary span.
+/// `read` loads a source file by the path rustc reported; each file is read once.
+pub fn attach_context<F>(diagnostics: &mut [Diagnostic], context: usize, mut read: F)
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut cache: HashMap<String, Option<String>> = HashMap::new();
+    for d in diagnostics {
+        let source = cache
+            .entry(d.span.file.clone())
+            .or_insert_with(|| read(&d.span.file));
+        d.context = snippet(&d.span, source.as_deref(), context);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(line_start: usize, line_end: usize, col_start: usize, col_end: usize) -> Span {
+        Span {
+            file: "src/lib.rs".to_string(),
+            line_start,
+            line_end,
+            col_start,
+            col_end,
+            text: vec!["    let x: i32 = \"a\";".to_string()],
+            label: None,
+        }
+    }
+
+    const SRC: &str = "fn main() {\n    let y = 1;\n    let x: i32 = \"a\";\n    drop(y);\n}\n";
+
+    #[test]
+    fn test_snippet_marks_columns_and_dedents() {
+        let lines = snippet(&span(3, 3, 18, 21), Some(SRC), 1);
+        assert_eq!(
+            lines,
+            vec!["2: let y = 1;", "3: let x: i32 = ⟦\"a\"⟧;", "4: drop(y);"]
+        );
+    }
+
+    #[test]
+    fn test_snippet_clamps_at_file_start() {
+        let lines = snippet(&span(1, 1, 4, 8), Some(SRC), 3);
+        assert_eq!(lines[0], "1: fn ⟦main⟧() {");
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn test_snippet_multiline_span() {
+        let lines = snippet(&span(2, 4, 5, 13), Some(SRC), 0);
+        assert_eq!(
+            lines,
+            vec!["2: ⟦let y = 1;", "3: let x: i32 = \"a\";", "4: drop(y);⟧"]
+        );
+    }
+
+    #[test]
+    fn test_snippet_marks_spans_starting_in_the_indentation() {
+        // From the middle of the four spaces before `let` to the end of `let y`
+        let lines = snippet(&span(2, 2, 3, 10), Some(SRC), 0);
+        assert_eq!(lines, vec!["2: ⟦let y⟧ = 1;"]);
+    }
+
+    #[test]
+    fn test_snippet_falls_back_to_span_text() {
+        let lines = snippet(&span(3, 3, 18, 21), None, 2);
+        assert_eq!(lines, vec!["3: let x: i32 = ⟦\"a\"⟧;"]);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::Diagnostic;
+use std::collections::HashMap;
+
+/// Collapses diagnostics that share a code, primary span and message, such as
+/// the same warning reported for both the lib and lib-test targets, or once per
+/// macro expansion. The first occurrence keeps its position; `count` and
+/// `targets` accumulate the rest.
+pub fn dedup_diagnostics(diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    let mut out: Vec<Diagnostic> = Vec::with_capacity(diagnostics.len());
+    let mut seen = HashMap::new();
+    for d in diagnostics {
+        let key = (d.code.clone(), d.span.clone(), d.message.clone());
+        match seen.get(&key) {
+            Some(&i) => {
+                let first: &mut Diagnostic = &mut out[i];
+                first.count += d.count;
+                for t in d.targets {
+                    if !first.targets.contains(&t) {
+                        first.targets.push(t);
+                    }
+                }
+            }
+            None => {
+                seen.insert(key, out.len());
+                out.push(d);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture_diagnostics;
+
+    #[test]
+    fn test_dedup_counts_repeats_and_merges_targets() {
+        let first = fixture_diagnostics();
+        let mut second = fixture_diagnostics();
+        for d in &mut second {
+            d.targets = vec!["benchmark_test_crate (test)".to_string()];
+        }
+        let mut all = first.clone();
+        all.extend(second);
+
+        let deduped = dedup_diagnostics(all);
+        assert_eq!(deduped.len(), first.len());
+        assert!(deduped.iter().all(|d| d.count == 2));
+        assert_eq!(
+            deduped[0].targets,
+            vec!["benchmark_test_crate (lib)", "benchmark_test_crate (test)"]
+        );
+        assert!(deduped[0].to_string().ends_with(
+            "[reported 2x in: benchmark_test_crate (lib); benchmark_test_crate (test)]"
+        ));
+    }
+
+    #[test]
+    fn test_dedup_keeps_distinct_spans() {
+        let diags = fixture_diagnostics();
+        let n = diags.len();
+        assert_eq!(dedup_diagnostics(diags).len(), n);
+    }
+}
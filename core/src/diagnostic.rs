@@ -0,0 +1,551 @@
+use crate::fix::Suggestion;
+use serde_json::{json, Value};
+use std::fmt;
+
+/// Severity of a compiler diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl Level {
+    /// Parses the `level` field of a rustc diagnostic.
+    /// Internal compiler errors are reported as plain errors.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "error" | "error: internal compiler error" => Some(Level::Error),
+            "warning" => Some(Level::Warning),
+            "note" => Some(Level::Note),
+            "help" => Some(Level::Help),
+            _ => None,
+        }
+    }
+
+    /// Lowercase name as used by rustc ("error", "warning", ...).
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Note => "note",
+            Level::Help => "help",
+        }
+    }
+
+    /// Capitalized label used in the compact string output.
+    pub fn label(self) -> &'static str {
+        match self {
+            Level::Error => "Error",
+            Level::Warning => "Warning",
+            Level::Note => "Note",
+            Level::Help => "Help",
+        }
+    }
+
+    /// Monaco-style marker severity (error = 5, warning = 4, ...).
+    pub fn severity(self) -> u8 {
+        match self {
+            Level::Error => 5,
+            Level::Warning => 4,
+            Level::Note => 2,
+            Level::Help => 1,
+        }
+    }
+}
+
+/// A source location, 1-based as reported by rustc.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub file: String,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub col_start: usize,
+    pub col_end: usize,
+    /// Source lines covered by the span, as included by rustc.
+    pub text: Vec<String>,
+    /// Label rustc attaches to the span, e.g. "expected `i32`, found `&str`".
+    pub label: Option<String>,
+}
+
+impl Span {
+    fn from_json(sp: &Value) -> Option<Self> {
+        Some(Span {
+            file: sp["file_name"].as_str()?.to_string(),
+            line_start: sp["line_start"].as_u64()? as usize,
+            line_end: sp["line_end"].as_u64()? as usize,
+            col_start: sp["column_start"].as_u64()? as usize,
+            col_end: sp["column_end"].as_u64()? as usize,
+            text: sp["text"]
+                .as_array()
+                .map(|lines| {
+                    lines
+                        .iter()
+                        .filter_map(|l| l["text"].as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            label: sp["label"].as_str().map(str::to_string),
+        })
+    }
+
+    /// Flat JSON object with `file`, `line_start`, `line_end`, `col_start`, `col_end`.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "file": self.file,
+            "line_start": self.line_start,
+            "line_end": self.line_end,
+            "col_start": self.col_start,
+            "col_end": self.col_end,
+        })
+    }
+}
+
+/// A child note/help attached to a diagnostic, with its location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelatedInfo {
+    pub level: Level,
+    pub message: String,
+    pub span: Span,
+}
+
+impl RelatedInfo {
+    /// The span fields plus `level` and `message`.
+    pub fn to_json(&self) -> Value {
+        let mut obj = self.span.to_json();
+        obj["level"] = json!(self.level.as_str());
+        obj["message"] = json!(self.message);
+        obj
+    }
+}
+
+/// A single compiler diagnostic extracted from a `compiler-message` record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub level: Level,
+    /// Error code or lint name, e.g. "E0308" or "unused_variables".
+    pub code: Option<String>,
+    /// Short message, e.g. "mismatched types".
+    pub message: String,
+    /// Full human-readable rendering from rustc, including the source excerpt.
+    pub rendered: String,
+    /// Primary span (the first span marked primary, or the first span).
+    pub span: Span,
+    pub related: Vec<RelatedInfo>,
+    /// Child `help`/`note` messages without a span, e.g. "if you wanted to use a crate ...".
+    pub notes: Vec<(Level, String)>,
+    /// Fix suggestions from child `help` messages.
+    pub suggestions: Vec<Suggestion>,
+    /// Name of the package that produced the diagnostic, from cargo's `package_id`.
+    pub package: Option<String>,
+    /// Distinct targets the diagnostic was reported for, e.g. "my_crate (lib)".
+    pub targets: Vec<String>,
+    /// Source lines around the primary span, filled in by [`attach_context`].
+    ///
+    /// [`attach_context`]: crate::attach_context
+    pub context: Vec<String>,
+    /// How many times the diagnostic was reported; above 1 only after [`dedup_diagnostics`].
+    ///
+    /// [`dedup_diagnostics`]: crate::dedup_diagnostics
+    pub count: usize,
+}
+
+/// Collapses every run of whitespace (including newlines) into a single space.
+pub fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Extracts the package name from a cargo package ID. Handles the current
+/// `path+file:///ws/foo#name@1.0.0` and `path+file:///ws/foo#1.0.0` forms (the
+/// latter when the name matches the directory) and the legacy `foo 1.0.0 (...)`.
+pub(crate) fn package_name(id: &str) -> Option<String> {
+    let name = match id.split_once('#') {
+        Some((_, fragment)) if fragment.contains('@') => fragment.split('@').next()?,
+        Some((url, _)) => url
+            .split(['?', '#'])
+            .next()?
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()?,
+        None => id.split_whitespace().next()?,
+    };
+    Some(name.to_string()).filter(|n| !n.is_empty())
+}
+
+/// Labels a cargo `target` object as "name (kind)", e.g. "my_crate (lib)".
+fn target_label(target: &Value) -> Option<String> {
+    let kinds: Vec<&str> = target["kind"]
+        .as_array()?
+        .iter()
+        .filter_map(Value::as_str)
+        .collect();
+    Some(format!(
+        "{} ({})",
+        target["name"].as_str()?,
+        kinds.join(", ")
+    ))
+}
+
+/// Picks the primary span of a diagnostic, falling back to the first one.
+fn primary_span(spans: &[Value]) -> Option<&Value> {
+    spans
+        .iter()
+        .find(|sp| sp["is_primary"].as_bool() == Some(true))
+        .or_else(|| spans.first())
+}
+
+/// Parses one JSON record from cargo's output stream.
+///
+/// Returns `None` for anything that is not a `compiler-message`, and for
+/// messages without a source span (e.g. "aborting due to N previous errors").
+pub fn parse_cargo_message(log: &Value) -> Option<Diagnostic> {
+    let msg = log
+        .get("message")
+        .filter(|_| log["reason"] == "compiler-message")?;
+    let level = Level::parse(msg["level"].as_str()?)?;
+    let span = Span::from_json(primary_span(msg["spans"].as_array()?)?)?;
+
+    let children = msg["children"].as_array().map(Vec::as_slice).unwrap_or(&[]);
+    let related = children
+        .iter()
+        .filter_map(|c| {
+            let sp = c["spans"].as_array()?.first()?;
+            Some(RelatedInfo {
+                level: c["level"]
+                    .as_str()
+                    .and_then(Level::parse)
+                    .unwrap_or(Level::Note),
+                message: c["message"].as_str()?.to_string(),
+                span: Span::from_json(sp)?,
+            })
+        })
+        .collect();
+    let notes = children
+        .iter()
+        .filter(|c| c["spans"].as_array().is_none_or(Vec::is_empty))
+        .filter_map(|c| {
+            Some((
+                Level::parse(c["level"].as_str()?)?,
+                c["message"].as_str()?.to_string(),
+            ))
+        })
+        .collect();
+    let suggestions = children.iter().filter_map(Suggestion::from_child).collect();
+
+    Some(Diagnostic {
+        level,
+        code: msg["code"]["code"].as_str().map(str::to_string),
+        message: msg["message"].as_str().unwrap_or("").to_string(),
+        rendered: msg["rendered"].as_str()?.to_string(),
+        span,
+        related,
+        notes,
+        suggestions,
+        package: log["package_id"].as_str().and_then(package_name),
+        targets: target_label(&log["target"]).into_iter().collect(),
+        context: Vec::new(),
+        count: 1,
+    })
+}
+
+/// Lint-level notes such as "`#[warn(unused_mut)]` (part of `#[warn(unused)]`) on by default".
+pub(crate) fn is_boilerplate(note: &str) -> bool {
+    note.starts_with("`#[") && note.ends_with("on by default")
+}
+
+/// First line of a child message; long lists ("the following other types
+/// implement trait ...") are cut to their heading.
+fn first_line(message: &str) -> String {
+    let mut lines = message.lines();
+    let first = collapse_whitespace(lines.next().unwrap_or(""));
+    if lines.next().is_some() {
+        format!("{} …", first.trim_end_matches(':'))
+    } else {
+        first
+    }
+}
+
+impl Diagnostic {
+    /// Short single-line form built from rustc's `message` and child help/note
+    /// text instead of `rendered`, so the location appears once and the source
+    /// excerpt and footers are left out:
+    /// `Error[E0425] in src/lib.rs:10:5-15: <message>; help: <child> ...`
+    pub fn compact(&self) -> String {
+        let mut out = self.level.label().to_string();
+        if let Some(code) = &self.code {
+            out.push_str(&format!("[{}]", code));
+        }
+        out.push_str(&format!(
+            " in {}:{}:{}-{}: {}",
+            self.span.file,
+            self.span.line_start,
+            self.span.col_start,
+            self.span.col_end,
+            self.headline()
+        ));
+        for r in &self.related {
+            out.push_str(&format!(
+                "; {}: {}",
+                r.level.as_str(),
+                first_line(&r.message)
+            ));
+            let replacement = self
+                .suggestions
+                .iter()
+                .filter(|s| s.message == r.message)
+                .flat_map(|s| &s.edits)
+                .find(|e| e.line_start == r.span.line_start && !e.replacement.is_empty());
+            if let Some(e) = replacement {
+                out.push_str(&format!(" `{}`", collapse_whitespace(&e.replacement)));
+            }
+            if r.span.file != self.span.file || r.span.line_start != self.span.line_start {
+                out.push_str(&format!(
+                    " (at {}:{}:{})",
+                    r.span.file, r.span.line_start, r.span.col_start
+                ));
+            }
+        }
+        for (level, note) in self.notes.iter().filter(|(_, n)| !is_boilerplate(n)) {
+            out.push_str(&format!("; {}: {}", level.as_str(), first_line(note)));
+        }
+        out.push_str(&self.suffix());
+        out
+    }
+
+    /// rustc's short message followed by the primary span label, when the label
+    /// adds something: "mismatched types (expected `i32`, found `String`)".
+    pub(crate) fn headline(&self) -> String {
+        let message = collapse_whitespace(&self.message);
+        match self
+            .span
+            .label
+            .as_deref()
+            .filter(|l| !l.is_empty() && !self.message.contains(l))
+        {
+            Some(label) => format!("{} ({})", message, collapse_whitespace(label)),
+            None => message,
+        }
+    }
+
+    /// [`headline`](Self::headline), then the help and note children that have
+    /// no location of their own, one per line.
+    pub(crate) fn message_with_notes(&self) -> String {
+        let mut text = self.headline();
+        for (level, note) in self.notes.iter().filter(|(_, n)| !is_boilerplate(n)) {
+            text.push_str(&format!("\n{}: {}", level.as_str(), note));
+        }
+        text
+    }
+
+    /// Context lines and the dedup count, shared by both string forms.
+    fn suffix(&self) -> String {
+        let mut out = String::new();
+        if !self.context.is_empty() {
+            out.push_str(&format!(" Context: {}", self.context.join(" | ")));
+        }
+        if self.count > 1 {
+            out.push_str(&format!(
+                " [reported {}x in: {}]",
+                self.count,
+                self.targets.join("; ")
+            ));
+        }
+        out
+    }
+
+    /// Structured form used by `cargo chec --format objects`.
+    pub fn to_json(&self) -> Value {
+        let mut obj = json!({ "level": self.level.as_str(), "code": self.code });
+        for (k, v) in self.span.to_json().as_object().into_iter().flatten() {
+            obj[k] = v.clone();
+        }
+        obj["message"] = json!(self.message);
+        obj["related"] = self.related.iter().map(RelatedInfo::to_json).collect();
+        obj["suggestions"] = self.suggestions.iter().map(Suggestion::to_json).collect();
+        if !self.context.is_empty() {
+            obj["context"] = json!(self.context);
+        }
+        obj["count"] = json!(self.count);
+        obj["targets"] = json!(self.targets);
+        obj
+    }
+}
+
+/// Parses newline-delimited cargo JSON output into diagnostics, in order.
+/// Lines that are not valid JSON or not compiler messages are skipped.
+pub fn parse_cargo_messages(input: &str) -> Vec<Diagnostic> {
+    input
+        .lines()
+        .filter_map(|l| serde_json::from_str::<Value>(l).ok())
+        .filter_map(|log| parse_cargo_message(&log))
+        .collect()
+}
+
+/// The compact single-line form emitted by `cargo chec`:
+/// `Error[E0425] (severity 5) in src/lib.rs:10:5-15: <rendered> Related: In ...`
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.level.label())?;
+        if let Some(code) = &self.code {
+            write!(f, "[{}]", code)?;
+        }
+        write!(
+            f,
+            " (severity {}) in {}:{}:{}-{}: {}",
+            self.level.severity(),
+            self.span.file,
+            self.span.line_start,
+            self.span.col_start,
+            self.span.col_end,
+            collapse_whitespace(&self.rendered)
+        )?;
+        for r in &self.related {
+            write!(
+                f,
+                " Related: In {}:{}:{}-{}: {}",
+                r.span.file,
+                r.span.line_start,
+                r.span.col_start,
+                r.span.col_end,
+                collapse_whitespace(&r.message)
+            )?;
+        }
+        write!(f, "{}", self.suffix())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture_diagnostics;
+
+    #[test]
+    fn test_parse_fixture_counts() {
+        let diags = fixture_diagnostics();
+        let errors = diags.iter().filter(|d| d.level == Level::Error).count();
+        let warnings = diags.iter().filter(|d| d.level == Level::Warning).count();
+        assert_eq!(errors, 13);
+        assert_eq!(warnings, 21);
+    }
+
+    #[test]
+    fn test_parse_skips_spanless_messages() {
+        // "failure-note" summaries have no spans and must not show up
+        let diags = fixture_diagnostics();
+        assert!(diags.iter().all(|d| !d
+            .message
+            .starts_with("Some errors have detailed explanations")));
+    }
+
+    #[test]
+    fn test_parse_ignores_non_json_and_other_reasons() {
+        let input = "not json\n{\"reason\":\"build-finished\",\"success\":true}\n";
+        assert!(parse_cargo_messages(input).is_empty());
+    }
+
+    #[test]
+    fn test_compact_string_format() {
+        let diags = fixture_diagnostics();
+        let unresolved = diags
+            .iter()
+            .find(|d| d.message.contains("unresolved import"))
+            .unwrap();
+        let s = unresolved.to_string();
+        assert!(
+            s.starts_with("Error[E0432] (severity 5) in src/lib.rs:94:5-22: error[E0432]"),
+            "{}",
+            s
+        );
+        assert!(!s.contains('\n'));
+    }
+
+    #[test]
+    fn test_compact_string_includes_lint_name() {
+        let diags = fixture_diagnostics();
+        let unused = diags
+            .iter()
+            .find(|d| d.code.as_deref() == Some("unused_variables"))
+            .unwrap();
+        assert!(unused
+            .to_string()
+            .starts_with("Warning[unused_variables] (severity 4) in src/lib.rs:"));
+    }
+
+    #[test]
+    fn test_compact_uses_message_and_children() {
+        let diags = fixture_diagnostics();
+        let unresolved = diags
+            .iter()
+            .find(|d| d.message.contains("unresolved import"))
+            .unwrap();
+        let s = unresolved.compact();
+        assert_eq!(
+            s,
+            "Error[E0432] in src/lib.rs:94:5-22: unresolved import `nonexistent_crate` \
+             (use of unresolved module or unlinked crate `nonexistent_crate`); \
+             help: if you wanted to use a crate named `nonexistent_crate`, use `cargo add \
+             nonexistent_crate` to add it to your `Cargo.toml`"
+        );
+        assert!(s.len() < unresolved.to_string().len());
+    }
+
+    #[test]
+    fn test_compact_drops_lint_boilerplate_and_keeps_replacement() {
+        let diags = fixture_diagnostics();
+        let unused = diags
+            .iter()
+            .find(|d| d.code.as_deref() == Some("unused_variables"))
+            .unwrap();
+        let s = unused.compact();
+        assert!(
+            s.contains("help: if this is intentional, prefix it with an underscore `_unused_var_")
+        );
+        assert!(!s.contains("on by default"), "{}", s);
+        assert!(!s.contains(" (at "), "{}", s);
+        assert!(diags
+            .iter()
+            .all(|d| !d.compact().contains("For more information")));
+    }
+
+    #[test]
+    fn test_package_name_forms() {
+        assert_eq!(
+            package_name("path+file:///ws/tools/broken-tests#benchmark-test-crate@0.1.0")
+                .as_deref(),
+            Some("benchmark-test-crate")
+        );
+        assert_eq!(
+            package_name("path+file:///tmp/dd#0.1.0").as_deref(),
+            Some("dd")
+        );
+        assert_eq!(
+            package_name("registry+https://github.com/rust-lang/crates.io-index#serde@1.0.228")
+                .as_deref(),
+            Some("serde")
+        );
+        assert_eq!(
+            package_name("dd 0.1.0 (path+file:///tmp/dd)").as_deref(),
+            Some("dd")
+        );
+    }
+
+    #[test]
+    fn test_to_json_object_shape() {
+        let diags = fixture_diagnostics();
+        let mismatched = diags
+            .iter()
+            .find(|d| d.message == "mismatched types")
+            .unwrap();
+        let obj = mismatched.to_json();
+        assert_eq!(obj["level"], "error");
+        assert_eq!(obj["code"], "E0308");
+        assert_eq!(obj["file"], "src/lib.rs");
+        assert!(obj["line_start"].as_u64().unwrap() > 0);
+        assert!(obj["col_end"].as_u64().unwrap() > obj["col_start"].as_u64().unwrap());
+        let related = obj["related"].as_array().unwrap();
+        assert!(related
+            .iter()
+            .all(|r| r["file"].is_string() && r["message"].is_string()));
+    }
+}
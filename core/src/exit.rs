@@ -0,0 +1,55 @@
+use std::process::ExitCode;
+
+/// How a wrapper run ended, mapped to the process exit code shared by the
+/// whole family so scripts can branch on status without parsing the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Nothing to report (exit 0).
+    Clean,
+    /// Diagnostics or test failures were reported (exit 1).
+    Found,
+    /// The underlying tool or build failed without anything to report, or
+    /// the wrapper itself failed (exit 2).
+    ToolFailure,
+    /// A configured threshold, such as minimum coverage, wasn't met (exit 3).
+    ThresholdViolation,
+}
+
+impl Outcome {
+    /// The exit code for this outcome. With `no_fail`, findings and threshold
+    /// violations exit 0; tool failures still exit 2.
+    pub fn code(self, no_fail: bool) -> u8 {
+        match self {
+            Outcome::Clean => 0,
+            Outcome::Found | Outcome::ThresholdViolation if no_fail => 0,
+            Outcome::Found => 1,
+            Outcome::ToolFailure => 2,
+            Outcome::ThresholdViolation => 3,
+        }
+    }
+
+    pub fn exit_code(self, no_fail: bool) -> ExitCode {
+        ExitCode::from(self.code(no_fail))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes() {
+        assert_eq!(Outcome::Clean.code(false), 0);
+        assert_eq!(Outcome::Found.code(false), 1);
+        assert_eq!(Outcome::ToolFailure.code(false), 2);
+        assert_eq!(Outcome::ThresholdViolation.code(false), 3);
+    }
+
+    #[test]
+    fn test_no_fail_keeps_tool_failures() {
+        assert_eq!(Outcome::Clean.code(true), 0);
+        assert_eq!(Outcome::Found.code(true), 0);
+        assert_eq!(Outcome::ThresholdViolation.code(true), 0);
+        assert_eq!(Outcome::ToolFailure.code(true), 2);
+    }
+}
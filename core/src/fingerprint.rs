@@ -0,0 +1,175 @@
+use crate::{collapse_whitespace, Diagnostic};
+use std::collections::HashMap;
+
+/// 64-bit FNV-1a: small, and stable across Rust versions and platforms,
+/// unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Strips the parts of a message that change between otherwise identical runs:
+/// whitespace, `file:line:col` suffixes and toolchain paths (`/rustc/<hash>/`).
+pub fn normalize_message(message: &str) -> String {
+    collapse_whitespace(message)
+        .split(' ')
+        .map(|word| strip_line_col(normalize_path(word)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Drops the toolchain commit hash from standard library paths:
+/// `/rustc/<hash>/library/std/src/macros.rs` -> `library/std/src/macros.rs`.
+pub fn normalize_path(path: &str) -> &str {
+    match path.strip_prefix("/rustc/") {
+        Some(rest) => rest.split_once('/').map_or(path, |(_, p)| p),
+        None => path,
+    }
+}
+
+/// `src/lib.rs:10:5` -> `src/lib.rs`; words without a `:line[:col]` suffix are unchanged.
+fn strip_line_col(word: &str) -> &str {
+    let mut rest = word;
+    for _ in 0..2 {
+        match rest.rsplit_once(':') {
+            Some((head, n))
+                if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) && head.contains('.') =>
+            {
+                rest = head
+            }
+            _ => break,
+        }
+    }
+    rest
+}
+
+/// Hex fingerprint of the diagnostic's code, file and normalized message. Line
+/// numbers are left out so it survives edits elsewhere in the file.
+pub fn fingerprint(d: &Diagnostic) -> String {
+    let key = format!(
+        "{}\0{}\0{}",
+        d.code.as_deref().unwrap_or(""),
+        normalize_path(&d.span.file),
+        normalize_message(&d.message)
+    );
+    format!("{:016x}", fnv1a(key.as_bytes()))
+}
+
+/// Like [`fingerprint`], but also keyed on the enclosing item (see
+/// [`enclosing_item`](crate::enclosing_item)), so the same warning in two
+/// functions of one file gets two fingerprints.
+pub fn item_fingerprint(d: &Diagnostic, item: &str) -> String {
+    let key = format!(
+        "{}\0{}\0{}\0{}",
+        d.code.as_deref().unwrap_or(""),
+        normalize_path(&d.span.file),
+        normalize_message(&d.message),
+        item
+    );
+    format!("{:016x}", fnv1a(key.as_bytes()))
+}
+
+/// [`item_fingerprint`] for each diagnostic, where `items` are their
+/// [`enclosing_items`](crate::enclosing_items), made unique within the list: the
+/// second and later diagnostics sharing one (e.g. two "mismatched types" in one
+/// function) get their occurrence index, counted in source order, mixed in. A
+/// repeat added in another function leaves the existing fingerprints alone.
+pub fn unique_fingerprints(diagnostics: &[Diagnostic], items: &[String]) -> Vec<String> {
+    let mut order: Vec<usize> = (0..diagnostics.len()).collect();
+    order.sort_by_key(|&i| {
+        (
+            diagnostics[i].span.line_start,
+            diagnostics[i].span.col_start,
+        )
+    });
+
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut result = vec![String::new(); diagnostics.len()];
+    for i in order {
+        let base = item_fingerprint(&diagnostics[i], &items[i]);
+        let n = seen.entry(base.clone()).or_default();
+        result[i] = match *n {
+            0 => base.clone(),
+            k => format!("{:016x}", fnv1a(format!("{}#{}", base, k).as_bytes())),
+        };
+        *n += 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture_diagnostics;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_normalize_message() {
+        assert_eq!(
+            normalize_message("expected  `i32`,\n found `String` at src/lib.rs:10:5"),
+            "expected `i32`, found `String` at src/lib.rs"
+        );
+        assert_eq!(
+            normalize_message("in /rustc/59807616e1fa/library/std/src/macros.rs:12"),
+            "in library/std/src/macros.rs"
+        );
+        assert_eq!(
+            normalize_message("takes 3 arguments: `a:1`"),
+            "takes 3 arguments: `a:1`"
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_line_shift() {
+        let diags = fixture_diagnostics();
+        let mut shifted = diags[0].clone();
+        shifted.span.line_start += 7;
+        shifted.span.line_end += 7;
+        assert_eq!(fingerprint(&diags[0]), fingerprint(&shifted));
+        assert_eq!(fingerprint(&diags[0]).len(), 16);
+
+        let mut other_toolchain = diags[0].clone();
+        other_toolchain.span.file = "/rustc/0123abcd/library/std/src/macros.rs".to_string();
+        let mut this_toolchain = other_toolchain.clone();
+        this_toolchain.span.file = "/rustc/59807616e1fa/library/std/src/macros.rs".to_string();
+        assert_eq!(fingerprint(&other_toolchain), fingerprint(&this_toolchain));
+    }
+
+    #[test]
+    fn test_unique_fingerprints_disambiguate_repeats() {
+        let diags = fixture_diagnostics();
+        let items = vec![String::new(); diags.len()];
+        let prints = unique_fingerprints(&diags, &items);
+        let distinct: HashSet<&String> = prints.iter().collect();
+        assert_eq!(distinct.len(), diags.len());
+
+        // Independent of the order diagnostics are listed in
+        let mut reversed = diags.clone();
+        reversed.reverse();
+        let mut again = unique_fingerprints(&reversed, &items);
+        again.reverse();
+        assert_eq!(prints, again);
+    }
+
+    #[test]
+    fn test_unique_fingerprints_keep_repeats_in_other_items() {
+        let diags = fixture_diagnostics();
+        let items: Vec<String> = (0..diags.len()).map(|i| format!("fn f{}", i)).collect();
+        let prints = unique_fingerprints(&diags, &items);
+
+        // The same diagnostic again, above all the others but in another function
+        let mut repeat = diags[diags.len() - 1].clone();
+        repeat.span.line_start = 1;
+        let mut added = diags.clone();
+        added.push(repeat);
+        let mut added_items = items.clone();
+        added_items.push("fn new".to_string());
+        let again = unique_fingerprints(&added, &added_items);
+        assert_eq!(again[..diags.len()], prints[..]);
+        assert!(!prints.contains(&again[diags.len()]));
+    }
+}
@@ -0,0 +1,121 @@
+use serde_json::{json, Value};
+
+/// How confident rustc is that a suggestion is correct, mirroring
+/// `suggestion_applicability` in the JSON output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Applicability {
+    MachineApplicable,
+    MaybeIncorrect,
+    HasPlaceholders,
+    Unspecified,
+}
+
+impl Applicability {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "MachineApplicable" => Applicability::MachineApplicable,
+            "MaybeIncorrect" => Applicability::MaybeIncorrect,
+            "HasPlaceholders" => Applicability::HasPlaceholders,
+            _ => Applicability::Unspecified,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Applicability::MachineApplicable => "MachineApplicable",
+            Applicability::MaybeIncorrect => "MaybeIncorrect",
+            Applicability::HasPlaceholders => "HasPlaceholders",
+            Applicability::Unspecified => "Unspecified",
+        }
+    }
+}
+
+/// A single text replacement: bytes `byte_start..byte_end` of `file` become `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Edit {
+    pub file: String,
+    pub byte_start: usize,
+    pub byte_end: usize,
+    /// 1-based line of `byte_start`, to help locate the edit without re-reading the file.
+    pub line_start: usize,
+    pub replacement: String,
+}
+
+impl Edit {
+    pub(crate) fn from_json(sp: &Value) -> Option<Self> {
+        Some(Edit {
+            file: sp["file_name"].as_str()?.to_string(),
+            byte_start: sp["byte_start"].as_u64()? as usize,
+            byte_end: sp["byte_end"].as_u64()? as usize,
+            line_start: sp["line_start"].as_u64()? as usize,
+            replacement: sp["suggested_replacement"].as_str()?.to_string(),
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "file": self.file,
+            "line": self.line_start,
+            "byte_start": self.byte_start,
+            "byte_end": self.byte_end,
+            "replacement": self.replacement,
+        })
+    }
+}
+
+/// A rustc suggestion: one or more edits that must be applied together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub message: String,
+    pub applicability: Applicability,
+    pub edits: Vec<Edit>,
+}
+
+impl Suggestion {
+    /// Collects the spans of a child message that carry a `suggested_replacement`.
+    /// Multipart suggestions may mix applicabilities; the least confident one wins.
+    pub(crate) fn from_child(child: &Value) -> Option<Self> {
+        let spans: Vec<&Value> = child["spans"]
+            .as_array()?
+            .iter()
+            .filter(|sp| sp["suggested_replacement"].is_string())
+            .collect();
+        let applicability = spans
+            .iter()
+            .map(|sp| Applicability::parse(sp["suggestion_applicability"].as_str().unwrap_or("")))
+            .max()?;
+        Some(Suggestion {
+            message: child["message"].as_str().unwrap_or("").to_string(),
+            applicability,
+            edits: spans.into_iter().filter_map(Edit::from_json).collect(),
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "message": self.message,
+            "applicability": self.applicability.as_str(),
+            "edits": self.edits.iter().map(Edit::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture_diagnostics;
+
+    #[test]
+    fn test_suggestions_are_parsed() {
+        let diags = fixture_diagnostics();
+        let unused_import = diags
+            .iter()
+            .find(|d| d.message == "unused import: `std::collections::HashMap`")
+            .unwrap();
+        let s = &unused_import.suggestions[0];
+        assert_eq!(s.applicability, Applicability::MachineApplicable);
+        assert_eq!(s.edits[0].replacement, "");
+        assert_eq!(s.edits[0].line_start, 7);
+        assert!(s.edits[0].byte_end > s.edits[0].byte_start);
+    }
+}
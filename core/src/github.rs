@@ -0,0 +1,157 @@
+use crate::diagnostic::is_boilerplate;
+use crate::{Diagnostic, Level};
+use std::fmt;
+
+/// A GitHub Actions workflow command that shows up as an inline annotation:
+/// `::error file=src/lib.rs,line=10,col=5,endLine=10,endColumn=9,title=...::message`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    /// "error", "warning" or "notice".
+    pub command: &'static str,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub col: Option<usize>,
+    pub end_line: Option<usize>,
+    pub end_column: Option<usize>,
+    pub title: Option<String>,
+    pub message: String,
+}
+
+impl Annotation {
+    /// An annotation without a location, e.g. for a build failure.
+    pub fn new(command: &'static str, title: Option<String>, message: String) -> Self {
+        Annotation {
+            command,
+            file: None,
+            line: None,
+            col: None,
+            end_line: None,
+            end_column: None,
+            title,
+            message,
+        }
+    }
+
+    /// Annotates the primary span. The title is rustc's header (`error[E0308]`);
+    /// the message is the short message and span label, then one line per child.
+    pub fn from_diagnostic(d: &Diagnostic) -> Self {
+        let command = match d.level {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Note | Level::Help => "notice",
+        };
+        let title = match &d.code {
+            Some(code) => format!("{}[{}]", d.level.as_str(), code),
+            None => d.level.as_str().to_string(),
+        };
+        let mut message = d.headline();
+        for r in &d.related {
+            message.push_str(&format!(
+                "\n{}: {} ({}:{}:{})",
+                r.level.as_str(),
+                r.message,
+                r.span.file,
+                r.span.line_start,
+                r.span.col_start
+            ));
+        }
+        for (level, note) in d.notes.iter().filter(|(_, n)| !is_boilerplate(n)) {
+            message.push_str(&format!("\n{}: {}", level.as_str(), note));
+        }
+        Annotation {
+            command,
+            file: Some(d.span.file.clone()),
+            line: Some(d.span.line_start),
+            col: Some(d.span.col_start),
+            end_line: Some(d.span.line_end),
+            end_column: Some(d.span.col_end),
+            title: Some(title),
+            message,
+        }
+    }
+}
+
+/// Escapes a command's message (`%`, CR and LF).
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes a property value, which additionally can't contain `:` or `,`.
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut properties = Vec::new();
+        if let Some(file) = &self.file {
+            properties.push(format!("file={}", escape_property(file)));
+        }
+        let numbers = [
+            ("line", self.line),
+            ("col", self.col),
+            ("endLine", self.end_line),
+            ("endColumn", self.end_column),
+        ];
+        for (name, value) in numbers {
+            if let Some(v) = value {
+                properties.push(format!("{}={}", name, v));
+            }
+        }
+        if let Some(title) = &self.title {
+            properties.push(format!("title={}", escape_property(title)));
+        }
+        write!(f, "::{}", self.command)?;
+        if !properties.is_empty() {
+            write!(f, " {}", properties.join(","))?;
+        }
+        write!(f, "::{}", escape_data(&self.message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture_diagnostics;
+
+    #[test]
+    fn test_diagnostic_annotation_line() {
+        let diags = fixture_diagnostics();
+        let mismatched = diags
+            .iter()
+            .find(|d| d.message == "mismatched types")
+            .unwrap();
+        assert_eq!(
+            Annotation::from_diagnostic(mismatched).to_string(),
+            "::error file=src/lib.rs,line=79,col=18,endLine=79,endColumn=46,title=error[E0308]\
+             ::mismatched types (expected `i32`, found `String`)"
+        );
+    }
+
+    #[test]
+    fn test_children_are_escaped_onto_new_lines() {
+        let diags = fixture_diagnostics();
+        let e0061 = diags
+            .iter()
+            .find(|d| d.code.as_deref() == Some("E0061"))
+            .unwrap();
+        let line = Annotation::from_diagnostic(e0061).to_string();
+        assert!(!line.contains('\n'));
+        assert!(line.contains("%0Anote: function defined here (src/lib.rs:135:41)"));
+    }
+
+    #[test]
+    fn test_property_escaping() {
+        let a = Annotation::new(
+            "error",
+            Some("Test failed: a::b, 100%".to_string()),
+            "left: 1\r\nright: 2".to_string(),
+        );
+        assert_eq!(
+            a.to_string(),
+            "::error title=Test failed%3A a%3A%3Ab%2C 100%25::left: 1%0D%0Aright: 2"
+        );
+    }
+}
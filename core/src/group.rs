@@ -0,0 +1,74 @@
+use crate::{Diagnostic, Level};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// Buckets items (usually diagnostics) by `key`, keeping groups in order of first appearance.
+pub fn group_by<T, F>(items: &[T], key: F) -> Vec<(String, Vec<&T>)>
+where
+    F: Fn(&T) -> String,
+{
+    let mut groups: Vec<(String, Vec<&T>)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for d in items {
+        let k = key(d);
+        match index.get(&k) {
+            Some(&i) => groups[i].1.push(d),
+            None => {
+                index.insert(k.clone(), groups.len());
+                groups.push((k, vec![d]));
+            }
+        }
+    }
+    groups
+}
+
+fn count_levels<'a>(diagnostics: impl IntoIterator<Item = &'a Diagnostic>) -> (usize, usize) {
+    diagnostics
+        .into_iter()
+        .fold((0, 0), |(e, w), d| match d.level {
+            Level::Error => (e + 1, w),
+            Level::Warning => (e, w + 1),
+            _ => (e, w),
+        })
+}
+
+/// Summary header for grouped output: total errors and warnings, and the same
+/// counts per crate (package).
+pub fn summarize(diagnostics: &[Diagnostic]) -> Value {
+    let (errors, warnings) = count_levels(diagnostics);
+    let mut crates = Map::new();
+    for (name, group) in group_by(diagnostics, |d: &Diagnostic| {
+        d.package.clone().unwrap_or_else(|| "(unknown)".to_string())
+    }) {
+        let (e, w) = count_levels(group);
+        crates.insert(name, json!({ "errors": e, "warnings": w }));
+    }
+    json!({ "errors": errors, "warnings": warnings, "crates": crates })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture_diagnostics;
+
+    #[test]
+    fn test_group_by_code_keeps_first_seen_order() {
+        let diags = fixture_diagnostics();
+        let groups = group_by(&diags, |d: &Diagnostic| d.code.clone().unwrap_or_default());
+        assert_eq!(groups[0].0, "E0432");
+        let e0308 = groups.iter().find(|(k, _)| k == "E0308").unwrap();
+        assert_eq!(e0308.1.len(), 4);
+        assert_eq!(
+            groups.iter().map(|(_, g)| g.len()).sum::<usize>(),
+            diags.len()
+        );
+    }
+
+    #[test]
+    fn test_summarize_counts_per_crate() {
+        let summary = summarize(&fixture_diagnostics());
+        assert_eq!(summary["errors"], 13);
+        assert_eq!(summary["warnings"], 21);
+        assert_eq!(summary["crates"]["benchmark-test-crate"]["errors"], 13);
+    }
+}
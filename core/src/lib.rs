@@ -0,0 +1,57 @@
+//! Shared parsing of `cargo --message-format=json` output.
+//!
+//! Used by `cargo-chec` and `cargo-tes` to turn `compiler-message` records into
+//! typed [`Diagnostic`]s, so the compact string format lives in one place.
+
+mod apply;
+mod assert_diff;
+mod baseline;
+mod budget;
+mod changes;
+mod context;
+mod dedup;
+mod diagnostic;
+mod exit;
+mod fingerprint;
+mod fix;
+mod github;
+mod group;
+mod lsp;
+mod panic;
+mod rank;
+mod report;
+mod rerun;
+mod sarif;
+
+pub use apply::{apply_edits, select_fixes, unified_diff, FixSelection};
+pub use assert_diff::{assert_diff, MIN_DIFF_CHARS, STRING_CONTEXT_CHARS};
+pub use baseline::{enclosing_item, enclosing_items, Baseline, BaselineEntry};
+pub use budget::{char_budget, fit_to_budget, Omitted, CHARS_PER_TOKEN};
+pub use changes::{git_changed_lines, workspace_root, ChangedLines};
+pub use context::{attach_context, snippet};
+pub use dedup::dedup_diagnostics;
+pub use diagnostic::{
+    collapse_whitespace, parse_cargo_message, parse_cargo_messages, Diagnostic, Level, RelatedInfo,
+    Span,
+};
+pub use exit::Outcome;
+pub use fingerprint::{
+    fingerprint, item_fingerprint, normalize_message, normalize_path, unique_fingerprints,
+};
+pub use fix::{Applicability, Edit, Suggestion};
+pub use github::Annotation;
+pub use group::{group_by, summarize};
+pub use lsp::{file_uri, publish_diagnostics};
+pub use panic::{parse_backtrace, parse_panic, Frame, Panic};
+pub use rank::{rank_by_root_cause, root_cause_scores, root_causes};
+pub use report::{to_checkstyle, to_codequality};
+pub use rerun::{FailedTests, TestTarget};
+pub use sarif::to_sarif;
+
+/// The parsed `cargo check` output of tools/broken-tests, shared by the unit tests.
+#[cfg(test)]
+pub(crate) fn fixture_diagnostics() -> Vec<Diagnostic> {
+    parse_cargo_messages(
+        &std::fs::read_to_string("../tools/broken-tests/fixtures/cargo-check.json").unwrap(),
+    )
+}
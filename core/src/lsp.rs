@@ -0,0 +1,202 @@
+use crate::group_by;
+use crate::sarif::help_uri;
+use crate::{Diagnostic, Level, Span};
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// LSP `DiagnosticSeverity`: Error = 1, Warning = 2, Information = 3, Hint = 4.
+fn lsp_severity(level: Level) -> u8 {
+    match level {
+        Level::Error => 1,
+        Level::Warning => 2,
+        Level::Note => 3,
+        Level::Help => 4,
+    }
+}
+
+/// Converts a 1-based char column into a 0-based UTF-16 offset, which is what
+/// LSP positions count by default. Without the line text, chars are assumed
+/// to be one UTF-16 unit each.
+fn utf16_character(line: Option<&str>, col: usize) -> usize {
+    let chars = col.saturating_sub(1);
+    match line {
+        Some(text) => {
+            let mut units = 0;
+            let mut seen = 0;
+            for c in text.chars().take(chars) {
+                units += c.len_utf16();
+                seen += 1;
+            }
+            units + (chars - seen)
+        }
+        None => chars,
+    }
+}
+
+/// 0-based LSP range of a span, using rustc's copy of the source lines.
+fn range(span: &Span) -> Value {
+    let first = span.text.first().map(String::as_str);
+    let last = span.text.last().map(String::as_str);
+    json!({
+        "start": {
+            "line": span.line_start.saturating_sub(1),
+            "character": utf16_character(first, span.col_start),
+        },
+        "end": {
+            "line": span.line_end.saturating_sub(1),
+            "character": utf16_character(last, span.col_end),
+        },
+    })
+}
+
+/// `DiagnosticTag`s editors use to grey out or strike through code:
+/// 1 = Unnecessary (unused code), 2 = Deprecated.
+fn tags(code: Option<&str>) -> Vec<u8> {
+    match code {
+        Some(c) if c.starts_with("unused_") || c == "dead_code" || c == "unreachable_code" => {
+            vec![1]
+        }
+        Some("deprecated") => vec![2],
+        _ => Vec::new(),
+    }
+}
+
+/// Percent-encodes an absolute path as a `file://` URI.
+pub fn file_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        // Windows drive paths: file:///C:/...
+        uri.push('/');
+    }
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(b as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    uri
+}
+
+fn lsp_diagnostic(d: &Diagnostic, uri: &impl Fn(&str) -> String) -> Value {
+    let mut diag = json!({
+        "range": range(&d.span),
+        "severity": lsp_severity(d.level),
+        "source": "rustc",
+        "message": d.message_with_notes(),
+    });
+    if let Some(code) = &d.code {
+        diag["code"] = json!(code);
+        if let Some(href) = help_uri(code) {
+            diag["codeDescription"] = json!({ "href": href });
+        }
+    }
+    let tags = tags(d.code.as_deref());
+    if !tags.is_empty() {
+        diag["tags"] = json!(tags);
+    }
+    if !d.related.is_empty() {
+        diag["relatedInformation"] = d
+            .related
+            .iter()
+            .map(|r| {
+                json!({
+                    "location": { "uri": uri(&r.span.file), "range": range(&r.span) },
+                    "message": format!("{}: {}", r.level.as_str(), r.message),
+                })
+            })
+            .collect();
+    }
+    diag
+}
+
+/// Groups diagnostics by file into `textDocument/publishDiagnostics` params,
+/// `{ "uri": ..., "diagnostics": [...] }`, in order of first appearance.
+/// `uri` maps a path as reported by rustc to a document URI (see [`file_uri`]).
+pub fn publish_diagnostics<F>(diagnostics: &[Diagnostic], uri: F) -> Vec<Value>
+where
+    F: Fn(&str) -> String,
+{
+    group_by(diagnostics, |d| d.span.file.clone())
+        .into_iter()
+        .map(|(file, group)| {
+            json!({
+                "uri": uri(&file),
+                "diagnostics": group
+                    .into_iter()
+                    .map(|d| lsp_diagnostic(d, &uri))
+                    .collect::<Vec<_>>(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture_diagnostics;
+
+    fn fixture_params() -> Vec<Value> {
+        publish_diagnostics(&fixture_diagnostics(), |f| format!("file:///ws/{}", f))
+    }
+
+    #[test]
+    fn test_one_params_object_per_file() {
+        let params = fixture_params();
+        // One warning's primary span is inside a std macro
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[0]["uri"], "file:///ws/src/lib.rs");
+        assert_eq!(params[0]["diagnostics"].as_array().unwrap().len(), 33);
+        assert!(params[1]["uri"]
+            .as_str()
+            .unwrap()
+            .ends_with("library/std/src/macros.rs"));
+    }
+
+    #[test]
+    fn test_zero_based_range_severity_and_related() {
+        let params = fixture_params();
+        let diags = params[0]["diagnostics"].as_array().unwrap();
+        let e0061 = diags.iter().find(|d| d["code"] == "E0061").unwrap();
+        // rustc: src/lib.rs:140:5-21
+        assert_eq!(
+            e0061["range"],
+            json!({ "start": { "line": 139, "character": 4 }, "end": { "line": 139, "character": 20 } })
+        );
+        assert_eq!(e0061["severity"], 1);
+        assert_eq!(e0061["source"], "rustc");
+        assert_eq!(
+            e0061["codeDescription"]["href"],
+            "https://doc.rust-lang.org/error_codes/E0061.html"
+        );
+        let related = &e0061["relatedInformation"][0];
+        assert_eq!(related["location"]["uri"], "file:///ws/src/lib.rs");
+        assert_eq!(related["location"]["range"]["start"]["line"], 134);
+        assert_eq!(related["message"], "note: function defined here");
+
+        let unused = diags
+            .iter()
+            .find(|d| d["code"] == "unused_variables")
+            .unwrap();
+        assert_eq!(unused["severity"], 2);
+        assert_eq!(unused["tags"], json!([1]));
+    }
+
+    #[test]
+    fn test_utf16_character_counts_surrogate_pairs() {
+        // "🦀" is one char but two UTF-16 units
+        assert_eq!(utf16_character(Some("let 🦀 = x;"), 9), 9);
+        assert_eq!(utf16_character(None, 9), 8);
+        assert_eq!(utf16_character(Some("ab"), 3), 2);
+    }
+
+    #[test]
+    fn test_file_uri_encoding() {
+        assert_eq!(
+            file_uri(Path::new("/home/me/my crate/src/lib.rs")),
+            "file:///home/me/my%20crate/src/lib.rs"
+        );
+    }
+}
@@ -0,0 +1,334 @@
+use serde_json::{json, Value};
+use std::fmt;
+use std::path::Path;
+
+/// Where and why a test panicked, parsed from its captured output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Panic {
+    /// Thread name, usually the test name (`<unnamed>` for spawned threads).
+    pub thread: String,
+    pub file: String,
+    pub line: usize,
+    pub col: usize,
+    /// The whole panic message, including any `left`/`right` lines.
+    pub message: String,
+    /// `assert_eq!`/`assert_ne!` operands, as printed with `{:?}`.
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+impl Panic {
+    pub fn to_json(&self) -> Value {
+        let mut obj = json!({
+            "thread": self.thread,
+            "file": self.file,
+            "line": self.line,
+            "col": self.col,
+            "message": self.message,
+        });
+        if let (Some(left), Some(right)) = (&self.left, &self.right) {
+            obj["left"] = json!(left);
+            obj["right"] = json!(right);
+        }
+        obj
+    }
+}
+
+/// Splits `path:line:col` from the right, so Windows drive letters survive.
+fn split_location(s: &str) -> Option<(String, usize, usize)> {
+    let (rest, col) = s.rsplit_once(':')?;
+    let (file, line) = rest.rsplit_once(':')?;
+    Some((file.to_string(), line.parse().ok()?, col.parse().ok()?))
+}
+
+/// Lines after the panic message that belong to the test harness, not the message.
+fn is_trailer(line: &str) -> bool {
+    line.starts_with("note: run with `RUST_BACKTRACE")
+        || line.starts_with("stack backtrace:")
+        || line.starts_with("thread '")
+}
+
+/// The `left`/`right` operands of a failed `assert_eq!`/`assert_ne!`:
+/// `assertion `left == right` failed\n  left: 4\n right: 5`, or before 1.73
+/// `assertion failed: `(left == right)`\n  left: `4`,\n right: `5``.
+fn assert_operands(message: &str) -> Option<(String, String)> {
+    let (head, rest) = message.split_once("\n  left: ")?;
+    let (left, right) = rest.split_once("\n right: ")?;
+    if head.starts_with("assertion failed: `(left") {
+        let unquote = |v: &str| {
+            let v = v.trim_end().trim_end_matches(',');
+            v.strip_prefix('`')
+                .and_then(|v| v.strip_suffix('`'))
+                .unwrap_or(v)
+                .to_string()
+        };
+        return Some((unquote(left), unquote(right)));
+    }
+    Some((left.to_string(), right.trim_end().to_string()))
+}
+
+/// Finds the first panic in a test's output. Handles the current format
+///
+/// ```text
+/// thread 'tests::it_works' (1234) panicked at src/lib.rs:10:5:
+/// assertion `left == right` failed
+/// ```
+///
+/// and the pre-1.73 `thread 'tests::it_works' panicked at 'message', src/lib.rs:10:5`.
+pub fn parse_panic(output: &str) -> Option<Panic> {
+    let start = output.find("thread '")?;
+    let header_and_rest = &output[start + "thread '".len()..];
+    let (thread, rest) = header_and_rest.split_once('\'')?;
+    let (_, rest) = rest.split_once("panicked at ")?;
+
+    let (message, (file, line, col)) = match rest.strip_prefix('\'') {
+        // Old format: the (possibly multi-line) message is quoted and the
+        // location follows it on the line that closes the quote
+        Some(quoted) => {
+            let mut offset = 0;
+            let mut end = None;
+            for line in quoted.split_inclusive('\n') {
+                if let Some((message, location)) = line.rsplit_once("', ") {
+                    if let Some(location) = split_location(location.trim_end()) {
+                        end = Some((offset + message.len(), location));
+                        break;
+                    }
+                }
+                offset += line.len();
+            }
+            let (end, location) = end?;
+            (quoted[..end].to_string(), location)
+        }
+        None => {
+            let (location, body) = rest.split_once('\n').unwrap_or((rest, ""));
+            let location = split_location(location.trim_end().trim_end_matches(':'))?;
+            let message: Vec<&str> = body.lines().take_while(|l| !is_trailer(l)).collect();
+            (message.join("\n").trim_end().to_string(), location)
+        }
+    };
+    let (left, right) = assert_operands(&message).unzip();
+    Some(Panic {
+        thread: thread.to_string(),
+        file,
+        line,
+        col,
+        message,
+        left,
+        right,
+    })
+}
+
+/// One frame of a `RUST_BACKTRACE=1` stack backtrace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Function path, without the `::h<hash>` suffix of full backtraces.
+    pub function: String,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+}
+
+impl Frame {
+    /// Whether the frame is in the workspace's own code rather than std, core,
+    /// the test harness or a dependency: rustc records workspace sources
+    /// relative to the workspace root, and anything else must be under `root`.
+    pub fn is_in_workspace(&self, root: &Path) -> bool {
+        let Some(file) = &self.file else {
+            return false;
+        };
+        let path = Path::new(file);
+        let in_tree = if path.is_absolute() {
+            path.starts_with(root) && !file.contains("/.cargo/registry/")
+        } else {
+            !file.starts_with("library/")
+        };
+        in_tree
+            && !["std::", "core::", "alloc::", "test::", "__rust"]
+                .iter()
+                .any(|prefix| self.function.starts_with(prefix))
+    }
+}
+
+/// The compact `file:line fn` form, e.g. `src/lib.rs:10 my_crate::tests::it_fails`.
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => {
+                let file = file.strip_prefix("./").unwrap_or(file);
+                write!(f, "{}:{} {}", file, line, self.function)
+            }
+            _ => write!(f, "{}", self.function),
+        }
+    }
+}
+
+/// Parses the first `stack backtrace:` in a test's output:
+///
+/// ```text
+/// stack backtrace:
+///    0: __rustc::rust_begin_unwind
+///              at /rustc/<hash>/library/std/src/panicking.rs:697:5
+///    1: my_crate::tests::it_fails
+///              at ./src/lib.rs:10:9
+/// ```
+pub fn parse_backtrace(output: &str) -> Vec<Frame> {
+    let mut frames: Vec<Frame> = Vec::new();
+    let Some(start) = output.find("stack backtrace:\n") else {
+        return frames;
+    };
+    for line in output[start..].lines().skip(1) {
+        let line = line.trim();
+        if let Some(location) = line.strip_prefix("at ") {
+            if let Some(frame) = frames.last_mut() {
+                let (file, line) = match split_location(location) {
+                    Some((file, line, _)) => (file, Some(line)),
+                    // Some frames only have `file:line`
+                    None => match location.rsplit_once(':') {
+                        Some((file, line)) => (file.to_string(), line.parse().ok()),
+                        None => (location.to_string(), None),
+                    },
+                };
+                frame.file = Some(file);
+                frame.line = line;
+            }
+            continue;
+        }
+        let Some((index, function)) = line.split_once(": ") else {
+            break;
+        };
+        if index.parse::<usize>().is_err() {
+            break;
+        }
+        let function = match function.rsplit_once("::h") {
+            Some((path, hash))
+                if hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                path
+            }
+            _ => function,
+        };
+        frames.push(Frame {
+            function: function.to_string(),
+            file: None,
+            line: None,
+        });
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_current_format_with_thread_id() {
+        let out = "STDOUT: hello\n\nthread 'test_failing_with_stdout' (12226) panicked at \
+                   tools/demo-outputs/tests/capture_tests.rs:12:5:\nassertion `left == right` \
+                   failed: Intentional failure\n  left: 4\n right: 5\nnote: run with \
+                   `RUST_BACKTRACE=1` environment variable to display a backtrace\n";
+        let p = parse_panic(out).unwrap();
+        assert_eq!(p.thread, "test_failing_with_stdout");
+        assert_eq!(p.file, "tools/demo-outputs/tests/capture_tests.rs");
+        assert_eq!((p.line, p.col), (12, 5));
+        assert_eq!(
+            p.message,
+            "assertion `left == right` failed: Intentional failure\n  left: 4\n right: 5"
+        );
+    }
+
+    #[test]
+    fn test_parse_old_format() {
+        let out = "thread 'tests::it_fails' panicked at 'boom', src/lib.rs:7:9\n";
+        let p = parse_panic(out).unwrap();
+        assert_eq!(p.thread, "tests::it_fails");
+        assert_eq!(p.message, "boom");
+        assert_eq!((p.file.as_str(), p.line, p.col), ("src/lib.rs", 7, 9));
+    }
+
+    #[test]
+    fn test_parse_assert_operands() {
+        let out = "thread 'eq' (7) panicked at src/lib.rs:3:5:\nassertion `left == right` \
+                   failed: sizes differ\n  left: [1, 2]\n right: [1, 2, 3]\n";
+        let p = parse_panic(out).unwrap();
+        assert_eq!(p.left.as_deref(), Some("[1, 2]"));
+        assert_eq!(p.right.as_deref(), Some("[1, 2, 3]"));
+        assert_eq!(p.to_json()["right"], "[1, 2, 3]");
+
+        let out = "thread 'ne' (7) panicked at src/lib.rs:4:5:\nassertion `left != right` \
+                   failed\n  left: \"a\"\n right: \"a\"\n";
+        let p = parse_panic(out).unwrap();
+        assert_eq!(
+            (p.left.unwrap(), p.right.unwrap()),
+            ("\"a\"".into(), "\"a\"".into())
+        );
+    }
+
+    #[test]
+    fn test_parse_old_format_assert_operands() {
+        let out = "thread 'eq' panicked at 'assertion failed: `(left == right)`\n  left: \
+                   `4`,\n right: `5`', src/lib.rs:9:5\nnote: run with `RUST_BACKTRACE=1`\n";
+        let p = parse_panic(out).unwrap();
+        assert_eq!((p.file.as_str(), p.line, p.col), ("src/lib.rs", 9, 5));
+        assert_eq!(p.left.as_deref(), Some("4"));
+        assert_eq!(p.right.as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn test_no_operands_without_assert_eq() {
+        let p = parse_panic("thread 't' (1) panicked at src/lib.rs:1:1:\nboom\n").unwrap();
+        assert!(p.to_json().get("left").is_none());
+        assert_eq!((p.left, p.right), (None, None));
+    }
+
+    const BACKTRACE: &str = "thread 'tests::it_fails' (9) panicked at src/lib.rs:10:9:\n\
+        boom\n\
+        stack backtrace:\n   \
+        0: __rustc::rust_begin_unwind\n             \
+        at /rustc/0123abcd/library/std/src/panicking.rs:697:5\n   \
+        1: core::panicking::panic_fmt\n             \
+        at /rustc/0123abcd/library/core/src/panicking.rs:75:14\n   \
+        2: my_crate::helper::h0123456789abcdef\n             \
+        at ./src/helper.rs:4:5\n   \
+        3: serde_json::de::from_str\n             \
+        at /home/me/.cargo/registry/src/index.crates.io-1949cf8c6b5b557f/serde_json-1.0.1/src/de.rs:2:3\n   \
+        4: my_crate::tests::it_fails\n             \
+        at /work/my_crate/src/lib.rs:10:9\n   \
+        5: core::ops::function::FnOnce::call_once\n             \
+        at /rustc/0123abcd/library/core/src/ops/function.rs:250:5\n\
+        note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.\n";
+
+    #[test]
+    fn test_parse_backtrace_frames() {
+        let frames = parse_backtrace(BACKTRACE);
+        assert_eq!(frames.len(), 6);
+        assert_eq!(frames[2].function, "my_crate::helper");
+        assert_eq!(frames[2].to_string(), "src/helper.rs:4 my_crate::helper");
+        assert_eq!(frames[5].line, Some(250));
+    }
+
+    #[test]
+    fn test_workspace_frames_only() {
+        let kept: Vec<String> = parse_backtrace(BACKTRACE)
+            .iter()
+            .filter(|f| f.is_in_workspace(Path::new("/work/my_crate")))
+            .map(|f| f.to_string())
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                "src/helper.rs:4 my_crate::helper",
+                "/work/my_crate/src/lib.rs:10 my_crate::tests::it_fails"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_panic_message_stops_at_backtrace() {
+        assert_eq!(parse_panic(BACKTRACE).unwrap().message, "boom");
+        assert!(parse_backtrace("no backtrace here").is_empty());
+    }
+
+    #[test]
+    fn test_no_panic() {
+        assert_eq!(parse_panic("test result: FAILED. exit code 1"), None);
+    }
+}
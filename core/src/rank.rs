@@ -0,0 +1,141 @@
+use crate::{Diagnostic, Level};
+use std::collections::HashSet;
+
+/// Parse errors: nothing after them in the file can be trusted.
+const SYNTAX_SCORE: u32 = 100;
+/// Unresolved imports, missing crates and module files.
+const IMPORT_SCORE: u32 = 80;
+/// The first error rustc reports in a file.
+const FIRST_IN_FILE_SCORE: u32 = 40;
+/// Per-position bonus for crates built earlier, i.e. dependencies of later ones.
+const UPSTREAM_STEP: u32 = 5;
+const UPSTREAM_MAX: u32 = 30;
+
+/// Errors scoring at least this much are reported by [`root_causes`].
+const ROOT_CAUSE_THRESHOLD: u32 = FIRST_IN_FILE_SCORE;
+
+fn is_syntax_error(d: &Diagnostic) -> bool {
+    d.code.is_none()
+        && (d.message.starts_with("expected ")
+            || d.message.starts_with("unexpected ")
+            || d.message.starts_with("unknown start of token")
+            || d.message.contains("unclosed delimiter")
+            || d.message.contains("mismatched closing delimiter"))
+}
+
+fn is_unresolved_import(d: &Diagnostic) -> bool {
+    // E0432 unresolved import, E0463 can't find crate, E0583 file not found for module
+    matches!(d.code.as_deref(), Some("E0432" | "E0463" | "E0583"))
+}
+
+/// Scores how likely each diagnostic is to be a root cause rather than a
+/// follow-on error. Warnings score 0; every error scores at least 1.
+///
+/// Cargo builds dependencies before their dependents, so the order in which
+/// packages first report errors stands in for the workspace dependency order.
+pub fn root_cause_scores(diagnostics: &[Diagnostic]) -> Vec<u32> {
+    let mut packages: Vec<Option<&str>> = Vec::new();
+    for d in diagnostics.iter().filter(|d| d.level == Level::Error) {
+        if !packages.contains(&d.package.as_deref()) {
+            packages.push(d.package.as_deref());
+        }
+    }
+
+    let mut seen_files = HashSet::new();
+    diagnostics
+        .iter()
+        .map(|d| {
+            if d.level != Level::Error {
+                return 0;
+            }
+            let mut score = 1;
+            if is_syntax_error(d) {
+                score += SYNTAX_SCORE;
+            }
+            if is_unresolved_import(d) {
+                score += IMPORT_SCORE;
+            }
+            if seen_files.insert(d.span.file.as_str()) {
+                score += FIRST_IN_FILE_SCORE;
+            }
+            let position = packages
+                .iter()
+                .position(|p| *p == d.package.as_deref())
+                .unwrap_or(0);
+            let downstream = (packages.len() - 1 - position) as u32;
+            score + (downstream * UPSTREAM_STEP).min(UPSTREAM_MAX)
+        })
+        .collect()
+}
+
+/// Reorders diagnostics so likely root causes come first. Ties keep rustc's order.
+pub fn rank_by_root_cause(diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    let scores = root_cause_scores(&diagnostics);
+    let mut scored: Vec<(u32, Diagnostic)> = scores.into_iter().zip(diagnostics).collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().map(|(_, d)| d).collect()
+}
+
+/// Keeps only the errors that look like root causes: syntax errors, unresolved
+/// imports and the first error in each file, ranked.
+pub fn root_causes(diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    let scores = root_cause_scores(&diagnostics);
+    let kept = scores
+        .into_iter()
+        .zip(diagnostics)
+        .filter(|(score, _)| *score >= ROOT_CAUSE_THRESHOLD)
+        .map(|(_, d)| d)
+        .collect();
+    rank_by_root_cause(kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture_diagnostics;
+
+    #[test]
+    fn test_unresolved_import_ranks_first_and_warnings_last() {
+        let ranked = rank_by_root_cause(fixture_diagnostics());
+        assert_eq!(ranked[0].code.as_deref(), Some("E0432"));
+        let first_warning = ranked
+            .iter()
+            .position(|d| d.level == Level::Warning)
+            .unwrap();
+        assert!(ranked[first_warning..]
+            .iter()
+            .all(|d| d.level == Level::Warning));
+    }
+
+    #[test]
+    fn test_syntax_error_outranks_import() {
+        let mut diags = fixture_diagnostics();
+        let mut syntax = diags[1].clone();
+        syntax.code = None;
+        syntax.message = "expected one of `,` or `}`, found `;`".to_string();
+        syntax.span.file = "src/other.rs".to_string();
+        diags.push(syntax);
+        let ranked = rank_by_root_cause(diags);
+        assert!(ranked[0].message.starts_with("expected one of"));
+        assert_eq!(ranked[1].code.as_deref(), Some("E0432"));
+    }
+
+    #[test]
+    fn test_upstream_package_errors_come_first() {
+        let mut diags = fixture_diagnostics();
+        let mut upstream = diags[2].clone();
+        upstream.package = Some("core-types".to_string());
+        upstream.span.file = "crates/core-types/src/lib.rs".to_string();
+        diags.insert(0, upstream);
+        let scores = root_cause_scores(&diags);
+        // first-in-file plus upstream bonus beats a plain first-in-file error downstream
+        assert!(scores[0] > FIRST_IN_FILE_SCORE + 1);
+    }
+
+    #[test]
+    fn test_root_causes_only_keeps_one_per_file_plus_imports() {
+        let roots = root_causes(fixture_diagnostics());
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0].code.as_deref(), Some("E0432"));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{unique_fingerprints, Diagnostic, Level};
+use serde_json::{json, Value};
+
+/// Escapes text for an XML attribute value, including newlines so multi-line
+/// messages survive attribute normalization.
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\n' => out.push_str("&#10;"),
+            '\r' => out.push_str("&#13;"),
+            '\t' => out.push_str("&#9;"),
+            // Other control characters are not allowed in XML 1.0 at all
+            c if (c as u32) < 0x20 => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Checkstyle `source` attribute: `rustc.E0308`, `rustc.unused_variables`, `clippy.needless_return`.
+fn checkstyle_source(code: Option<&str>) -> String {
+    match code {
+        Some(c) => match c.strip_prefix("clippy::") {
+            Some(lint) => format!("clippy.{}", lint),
+            None => format!("rustc.{}", c),
+        },
+        None => "rustc".to_string(),
+    }
+}
+
+/// Renders a Checkstyle XML report (as consumed by Jenkins' Warnings NG and
+/// similar), with one `<file>` per source file in order of first appearance.
+pub fn to_checkstyle(diagnostics: &[Diagnostic]) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<checkstyle version=\"4.3\">\n");
+    for (file, group) in crate::group_by(diagnostics, |d| d.span.file.clone()) {
+        out.push_str(&format!("  <file name=\"{}\">\n", xml_escape(&file)));
+        for d in group {
+            let severity = match d.level {
+                Level::Error => "error",
+                Level::Warning => "warning",
+                Level::Note | Level::Help => "info",
+            };
+            out.push_str(&format!(
+                "    <error line=\"{}\" column=\"{}\" severity=\"{}\" message=\"{}\" source=\"{}\"/>\n",
+                d.span.line_start,
+                d.span.col_start,
+                severity,
+                xml_escape(&d.message_with_notes()),
+                xml_escape(&checkstyle_source(d.code.as_deref()))
+            ));
+        }
+        out.push_str("  </file>\n");
+    }
+    out.push_str("</checkstyle>\n");
+    out
+}
+
+/// Builds a GitLab Code Quality report: a JSON array of issues with `description`,
+/// `check_name`, `fingerprint`, `severity` and `location`. Fingerprints come from
+/// [`unique_fingerprints`] with the diagnostics' enclosing `items`, so they stay
+/// stable when lines shift.
+pub fn to_codequality(diagnostics: &[Diagnostic], items: &[String]) -> Value {
+    let fingerprints = unique_fingerprints(diagnostics, items);
+    diagnostics
+        .iter()
+        .zip(fingerprints)
+        .map(|(d, fingerprint)| {
+            let severity = match d.level {
+                Level::Error => "critical",
+                Level::Warning => "minor",
+                Level::Note | Level::Help => "info",
+            };
+            json!({
+                "description": d.message_with_notes(),
+                "check_name": d.code.as_deref().unwrap_or("rustc"),
+                "fingerprint": fingerprint,
+                "severity": severity,
+                "location": {
+                    "path": d.span.file,
+                    "positions": {
+                        "begin": { "line": d.span.line_start, "column": d.span.col_start },
+                        "end": { "line": d.span.line_end, "column": d.span.col_end },
+                    },
+                },
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture_diagnostics;
+
+    #[test]
+    fn test_checkstyle_structure_and_escaping() {
+        let xml = to_checkstyle(&fixture_diagnostics());
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<checkstyle"));
+        assert!(xml.ends_with("</checkstyle>\n"));
+        assert_eq!(
+            xml.matches("<file ").count(),
+            xml.matches("</file>").count()
+        );
+        assert_eq!(xml.matches("<error ").count(), 34);
+        assert!(xml.contains(
+            "<error line=\"79\" column=\"18\" severity=\"error\" message=\"mismatched types \
+             (expected `i32`, found `String`)\" source=\"rustc.E0308\"/>"
+        ));
+        // Messages with quotes, '<' and newlines are escaped inside the attribute
+        assert!(xml.contains("source=\"rustc.unused_variables\""));
+        assert!(!xml.contains("message=\"\n"));
+        assert!(xml.contains("`{integer}`&#10;help: "), "{}", xml);
+    }
+
+    #[test]
+    fn test_xml_escape() {
+        assert_eq!(
+            xml_escape("a < b && \"c\"\n\u{1}"),
+            "a &lt; b &amp;&amp; &quot;c&quot;&#10;"
+        );
+        assert_eq!(
+            checkstyle_source(Some("clippy::needless_return")),
+            "clippy.needless_return"
+        );
+    }
+
+    #[test]
+    fn test_codequality_issue_shape() {
+        let diags = fixture_diagnostics();
+        let report = to_codequality(&diags, &vec![String::new(); diags.len()]);
+        let issues = report.as_array().unwrap();
+        assert_eq!(issues.len(), 34);
+        let first = &issues[0];
+        assert_eq!(first["check_name"], "E0432");
+        assert_eq!(first["severity"], "critical");
+        assert_eq!(first["location"]["path"], "src/lib.rs");
+        assert_eq!(first["location"]["positions"]["begin"]["line"], 94);
+        assert_eq!(first["fingerprint"].as_str().unwrap().len(), 16);
+        let warning = issues
+            .iter()
+            .find(|i| i["check_name"] == "unused_mut")
+            .unwrap();
+        assert_eq!(warning["severity"], "minor");
+    }
+}
@@ -0,0 +1,218 @@
+use crate::diagnostic::package_name;
+use crate::workspace_root;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Version of the state file format written by [`FailedTests::to_json`].
+const FAILED_TESTS_VERSION: u64 = 1;
+
+/// A test binary as cargo selects it: one target of one package.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TestTarget {
+    pub package: String,
+    /// `lib`, `bin`, `test`, `bench`, `example`, or `doc` for a lib's doc-tests
+    pub kind: String,
+    pub name: String,
+}
+
+impl TestTarget {
+    /// The test binary built by a `compiler-artifact` message, with its file
+    /// name as cargo prints it in `Running … (target/debug/deps/<name>)`.
+    pub fn from_artifact(msg: &Value) -> Option<(String, TestTarget)> {
+        if msg["reason"] != "compiler-artifact" || msg["profile"]["test"] != true {
+            return None;
+        }
+        let executable = Path::new(msg["executable"].as_str()?);
+        let kind = match msg["target"]["kind"][0].as_str()? {
+            "bin" => "bin",
+            "test" => "test",
+            "bench" => "bench",
+            "example" => "example",
+            _ => "lib",
+        };
+        let target = TestTarget {
+            package: package_name(msg["package_id"].as_str()?)?,
+            kind: kind.to_string(),
+            name: msg["target"]["name"].as_str()?.to_string(),
+        };
+        Some((
+            executable.file_name()?.to_string_lossy().into_owned(),
+            target,
+        ))
+    }
+
+    /// `cargo test` arguments selecting just this target.
+    pub fn cargo_args(&self) -> Vec<String> {
+        let mut args = vec!["--package".to_string(), self.package.clone()];
+        match self.kind.as_str() {
+            "lib" | "doc" => args.push(format!("--{}", self.kind)),
+            kind => args.extend([format!("--{}", kind), self.name.clone()]),
+        }
+        args
+    }
+}
+
+/// The `--target-dir` among cargo's arguments, before any `--`.
+fn target_dir_arg(cargo_args: &[String]) -> Option<PathBuf> {
+    let mut args = cargo_args.iter().take_while(|a| *a != "--");
+    while let Some(arg) = args.next() {
+        if arg == "--target-dir" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(dir) = arg.strip_prefix("--target-dir=") {
+            return Some(PathBuf::from(dir));
+        }
+    }
+    None
+}
+
+/// The failed tests of a run, grouped by the target they ran in; what
+/// `cargo tes --rerun-failed` runs again. Tests whose target couldn't be
+/// told are under `None` and rerun without a target selection.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FailedTests {
+    pub targets: BTreeMap<Option<TestTarget>, BTreeSet<String>>,
+}
+
+impl FailedTests {
+    /// Where the failed tests of the last run are kept: `cargo-tes/failed-tests.json`
+    /// in the target directory, as set by `--target-dir` in `cargo_args`,
+    /// `$CARGO_TARGET_DIR` or the workspace's `target`.
+    pub fn path(cargo_args: &[String]) -> Result<PathBuf, String> {
+        let target_dir = target_dir_arg(cargo_args)
+            .or_else(|| std::env::var_os("CARGO_TARGET_DIR").map(PathBuf::from));
+        let target_dir = match target_dir {
+            Some(dir) => dir,
+            None => workspace_root()?.join("target"),
+        };
+        Ok(target_dir.join("cargo-tes").join("failed-tests.json"))
+    }
+
+    pub fn insert(&mut self, target: Option<TestTarget>, test: &str) {
+        self.targets
+            .entry(target)
+            .or_default()
+            .insert(test.to_string());
+    }
+
+    /// Number of failed tests.
+    pub fn len(&self) -> usize {
+        self.targets.values().map(BTreeSet::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn to_json(&self) -> Value {
+        let targets: Vec<Value> = self
+            .targets
+            .iter()
+            .map(|(target, tests)| {
+                let mut entry = json!({ "tests": tests });
+                if let Some(t) = target {
+                    entry["package"] = json!(t.package);
+                    entry["kind"] = json!(t.kind);
+                    entry["name"] = json!(t.name);
+                }
+                entry
+            })
+            .collect();
+        json!({ "version": FAILED_TESTS_VERSION, "targets": targets })
+    }
+
+    pub fn from_json(v: &Value) -> Result<Self, String> {
+        match v["version"].as_u64() {
+            Some(FAILED_TESTS_VERSION) => {}
+            Some(other) => return Err(format!("unsupported failed tests version {}", other)),
+            None => {
+                return Err("not a cargo-tes failed tests file (missing \"version\")".to_string())
+            }
+        }
+        let mut failed = FailedTests::default();
+        for e in v["targets"].as_array().ok_or("missing \"targets\" array")? {
+            let target = match (e["package"].as_str(), e["kind"].as_str()) {
+                (Some(package), Some(kind)) => Some(TestTarget {
+                    package: package.to_string(),
+                    kind: kind.to_string(),
+                    name: e["name"].as_str().unwrap_or("").to_string(),
+                }),
+                _ => None,
+            };
+            for test in e["tests"].as_array().ok_or("target without \"tests\"")? {
+                failed.insert(
+                    target.clone(),
+                    test.as_str().ok_or("test name isn't a string")?,
+                );
+            }
+        }
+        Ok(failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artifact(package_id: &str, kind: &str, name: &str) -> Value {
+        json!({
+            "reason": "compiler-artifact",
+            "package_id": package_id,
+            "target": { "kind": [kind], "name": name },
+            "profile": { "test": true },
+            "executable": format!("/ws/target/debug/deps/{}-79a38eab25ff968c", name),
+        })
+    }
+
+    #[test]
+    fn test_target_dir_arg() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            target_dir_arg(&args(&["--release", "--target-dir", "/tmp/t"])),
+            Some(PathBuf::from("/tmp/t"))
+        );
+        assert_eq!(
+            target_dir_arg(&args(&["--target-dir=out"])),
+            Some(PathBuf::from("out"))
+        );
+        assert_eq!(target_dir_arg(&args(&["--", "--target-dir", "x"])), None);
+    }
+
+    #[test]
+    fn test_target_from_artifact() {
+        let (exe, target) =
+            TestTarget::from_artifact(&artifact("path+file:///ws/demo#0.1.0", "test", "integ"))
+                .unwrap();
+        assert_eq!(exe, "integ-79a38eab25ff968c");
+        assert_eq!(
+            target.cargo_args(),
+            ["--package", "demo", "--test", "integ"]
+        );
+
+        let (_, lib) =
+            TestTarget::from_artifact(&artifact("path+file:///ws/demo#0.1.0", "rlib", "demo"))
+                .unwrap();
+        assert_eq!(lib.cargo_args(), ["--package", "demo", "--lib"]);
+
+        let mut build = artifact("path+file:///ws/demo#0.1.0", "lib", "demo");
+        build["profile"]["test"] = json!(false);
+        assert_eq!(TestTarget::from_artifact(&build), None);
+    }
+
+    #[test]
+    fn test_failed_tests_round_trip() {
+        let mut failed = FailedTests::default();
+        let lib = TestTarget {
+            package: "demo".to_string(),
+            kind: "lib".to_string(),
+            name: "demo".to_string(),
+        };
+        failed.insert(Some(lib.clone()), "tests::b");
+        failed.insert(Some(lib), "tests::a");
+        failed.insert(None, "it_works");
+        assert_eq!(failed.len(), 3);
+        assert_eq!(FailedTests::from_json(&failed.to_json()).unwrap(), failed);
+        assert!(FailedTests::from_json(&json!({ "targets": [] })).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use crate::{Applicability, Diagnostic, Level, Span};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const INFORMATION_URI: &str = "https://github.com/permissionlessweb/cargo-chec";
+
+/// SARIF `level` for a rustc level; help and note both map to "note".
+fn sarif_level(level: Level) -> &'static str {
+    match level {
+        Level::Error => "error",
+        Level::Warning => "warning",
+        Level::Note | Level::Help => "note",
+    }
+}
+
+/// Documentation link for an error code or clippy lint, if there is a stable one.
+pub(crate) fn help_uri(code: &str) -> Option<String> {
+    if code.len() == 5 && code.starts_with('E') && code[1..].bytes().all(|b| b.is_ascii_digit()) {
+        Some(format!(
+            "https://doc.rust-lang.org/error_codes/{}.html",
+            code
+        ))
+    } else {
+        code.strip_prefix("clippy::").map(|lint| {
+            format!(
+                "https://rust-lang.github.io/rust-clippy/master/index.html#{}",
+                lint
+            )
+        })
+    }
+}
+
+/// rustc reports paths relative to the workspace root (`%SRCROOT%`), or absolute
+/// for files outside it such as the standard library.
+fn artifact_location(file: &str) -> Value {
+    let escaped = file.replace('%', "%25").replace(' ', "%20");
+    if file.starts_with('/') {
+        json!({ "uri": format!("file://{}", escaped) })
+    } else {
+        json!({ "uri": escaped, "uriBaseId": "%SRCROOT%" })
+    }
+}
+
+fn physical_location(span: &Span) -> Value {
+    json!({
+        "artifactLocation": artifact_location(&span.file),
+        "region": {
+            "startLine": span.line_start,
+            "startColumn": span.col_start,
+            "endLine": span.line_end,
+            "endColumn": span.col_end,
+        },
+    })
+}
+
+fn result(d: &Diagnostic, rule_index: Option<usize>) -> Value {
+    let mut result = json!({
+        "level": sarif_level(d.level),
+        "message": { "text": d.message_with_notes() },
+        "locations": [{ "physicalLocation": physical_location(&d.span) }],
+    });
+    if let (Some(code), Some(index)) = (&d.code, rule_index) {
+        result["ruleId"] = json!(code);
+        result["ruleIndex"] = json!(index);
+    }
+    if !d.related.is_empty() {
+        result["relatedLocations"] = d
+            .related
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                json!({
+                    "id": i,
+                    "physicalLocation": physical_location(&r.span),
+                    "message": { "text": format!("{}: {}", r.level.as_str(), r.message) },
+                })
+            })
+            .collect();
+    }
+    let fixes: Vec<Value> = d
+        .suggestions
+        .iter()
+        // Placeholder text (`/* i32 */`) isn't code a SARIF viewer should apply
+        .filter(|s| {
+            matches!(
+                s.applicability,
+                Applicability::MachineApplicable | Applicability::MaybeIncorrect
+            )
+        })
+        .filter(|s| !s.edits.is_empty())
+        .map(|s| {
+            let mut by_file: BTreeMap<&str, Vec<Value>> = BTreeMap::new();
+            for e in &s.edits {
+                by_file.entry(&e.file).or_default().push(json!({
+                    "deletedRegion": {
+                        "byteOffset": e.byte_start,
+                        "byteLength": e.byte_end - e.byte_start,
+                    },
+                    "insertedContent": { "text": e.replacement },
+                }));
+            }
+            json!({
+                "description": { "text": s.message },
+                "artifactChanges": by_file
+                    .into_iter()
+                    .map(|(file, replacements)| json!({
+                        "artifactLocation": artifact_location(file),
+                        "replacements": replacements,
+                    }))
+                    .collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+    if !fixes.is_empty() {
+        result["fixes"] = json!(fixes);
+    }
+    if d.count > 1 {
+        result["occurrenceCount"] = json!(d.count);
+    }
+    result
+}
+
+/// Builds a SARIF 2.1.0 log with a single run: one rule per distinct error code
+/// or lint name (in order of first appearance), one result per diagnostic, child
+/// spans as `relatedLocations` and rustc suggestions without placeholders as `fixes`.
+pub fn to_sarif(diagnostics: &[Diagnostic], tool_name: &str, tool_version: &str) -> Value {
+    let mut codes: Vec<&str> = Vec::new();
+    for code in diagnostics.iter().filter_map(|d| d.code.as_deref()) {
+        if !codes.contains(&code) {
+            codes.push(code);
+        }
+    }
+    let rules: Vec<Value> = codes
+        .iter()
+        .map(|code| {
+            let mut rule = json!({ "id": code });
+            if let Some(uri) = help_uri(code) {
+                rule["helpUri"] = json!(uri);
+            }
+            rule
+        })
+        .collect();
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|d| {
+            let index = d
+                .code
+                .as_deref()
+                .and_then(|c| codes.iter().position(|known| *known == c));
+            result(d, index)
+        })
+        .collect();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": tool_name,
+                    "version": tool_version,
+                    "informationUri": INFORMATION_URI,
+                    "rules": rules,
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture_diagnostics;
+    use std::fs;
+
+    fn fixture_log() -> Value {
+        to_sarif(&fixture_diagnostics(), "cargo-chec", "0.0.0")
+    }
+
+    fn validate(log: &Value) -> Vec<String> {
+        let schema: Value = serde_json::from_str(
+            &fs::read_to_string("fixtures/sarif-2.1.0-subset.schema.json").unwrap(),
+        )
+        .unwrap();
+        let compiled = jsonschema::JSONSchema::compile(&schema).unwrap();
+        let errors = match compiled.validate(log) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .map(|e| format!("{} at {}", e, e.instance_path))
+                .collect(),
+        };
+        errors
+    }
+
+    #[test]
+    fn test_sarif_validates_against_schema() {
+        let errors = validate(&fixture_log());
+        assert!(errors.is_empty(), "{:#?}", errors);
+    }
+
+    #[test]
+    fn test_schema_rejects_malformed_log() {
+        let mut log = fixture_log();
+        log["runs"][0]["results"][0]["level"] = json!("fatal");
+        log["runs"][0]["results"][1]["locations"][0]["physicalLocation"]["region"]["startLine"] =
+            json!(0);
+        assert_eq!(validate(&log).len(), 2);
+    }
+
+    #[test]
+    fn test_rules_are_unique_and_indexed() {
+        let log = fixture_log();
+        let run = &log["runs"][0];
+        let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 34);
+        for r in results {
+            let index = r["ruleIndex"].as_u64().unwrap() as usize;
+            assert_eq!(rules[index]["id"], r["ruleId"]);
+        }
+        let e0308 = rules.iter().find(|r| r["id"] == "E0308").unwrap();
+        assert_eq!(
+            e0308["helpUri"],
+            "https://doc.rust-lang.org/error_codes/E0308.html"
+        );
+        assert!(rules
+            .iter()
+            .find(|r| r["id"] == "unused_variables")
+            .unwrap()
+            .get("helpUri")
+            .is_none());
+    }
+
+    #[test]
+    fn test_related_locations_and_fixes() {
+        let log = fixture_log();
+        let results = log["runs"][0]["results"].as_array().unwrap();
+        let e0061 = results.iter().find(|r| r["ruleId"] == "E0061").unwrap();
+        let related = e0061["relatedLocations"].as_array().unwrap();
+        assert_eq!(related[0]["message"]["text"], "note: function defined here");
+        assert_eq!(
+            related[0]["physicalLocation"]["artifactLocation"]["uriBaseId"],
+            "%SRCROOT%"
+        );
+
+        let unused = results
+            .iter()
+            .find(|r| r["ruleId"] == "unused_variables")
+            .unwrap();
+        let replacement = &unused["fixes"][0]["artifactChanges"][0]["replacements"][0];
+        assert!(replacement["insertedContent"]["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("_unused_var_"));
+        assert!(replacement["deletedRegion"]["byteLength"].as_u64().unwrap() > 0);
+
+        // `takes_three_args(1, 2, /* i32 */)` has a placeholder, so it's no fix
+        assert!(e0061.get("fixes").is_none());
+    }
+}
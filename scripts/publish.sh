@@ -1,6 +1,8 @@
 #!/bin/sh
 set -e
 echo "Publishing all wrappers to crates.io..."
+echo "  - cargo-chec-core..."
+cargo publish -p cargo-chec-core
 echo "  - cargo-chec..."
 cargo publish -p cargo-chec
 echo "  - cargo-tes..."
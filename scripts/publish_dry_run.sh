@@ -1,6 +1,8 @@
 #!/bin/sh
 set -e
 echo "Running publish dry-run for all wrappers..."
+echo "  - cargo-chec-core..."
+cargo publish -p cargo-chec-core --dry-run
 echo "  - cargo-chec..."
 cargo publish -p cargo-chec --dry-run
 echo "  - cargo-tes..."
@@ -23,6 +23,7 @@ fn test_failing_with_stderr() {
 
 #[test]
 #[ignore = "fixture test for cargo-tes (intentionally fails)"]
+#[allow(clippy::assertions_on_constants)]
 fn test_failing_with_both_streams() {
     println!("STDOUT: Normal output line 1");
     eprintln!("STDERR: Error output line 1");
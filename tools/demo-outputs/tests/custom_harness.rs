@@ -0,0 +1,6 @@
+// A test binary with `harness = false`: cargo announces it like any other,
+// but it prints no libtest events. Fixture for cargo tes --rerun-failed.
+
+fn main() {
+    println!("custom harness: 1 check passed");
+}
@@ -0,0 +1,26 @@
+// Fixture tests for cargo tes --retries.
+// With CARGO_TES_FLAKY_FILE set to a counter file, the first fails on its
+// first run and passes on the next; with CARGO_TES_ALWAYS_FAIL set, the
+// second fails on every run. Otherwise both pass.
+
+use std::fs;
+
+#[test]
+fn test_flaky_on_first_run() {
+    let Some(counter) = std::env::var_os("CARGO_TES_FLAKY_FILE") else {
+        return;
+    };
+    let runs: u32 = fs::read_to_string(&counter)
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0);
+    fs::write(&counter, (runs + 1).to_string()).unwrap();
+    assert!(runs > 0, "Intentional failure on the first run");
+}
+
+#[test]
+fn test_failing_on_every_run() {
+    if std::env::var_os("CARGO_TES_ALWAYS_FAIL").is_some() {
+        panic!("Intentional failure on every run");
+    }
+}
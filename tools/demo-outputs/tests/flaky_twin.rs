@@ -0,0 +1,9 @@
+// Fixture test for cargo tes --retries: named like the flaky test in
+// flaky_tests.rs, but with CARGO_TES_ALWAYS_FAIL set it fails on every run.
+
+#[test]
+fn test_flaky_on_first_run() {
+    if std::env::var_os("CARGO_TES_ALWAYS_FAIL").is_some() {
+        panic!("Intentional failure on every run");
+    }
+}
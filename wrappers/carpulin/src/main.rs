@@ -1,3 +1,4 @@
+use cargo_chec_core::{char_budget, fit_to_budget, Outcome};
 use clap::Parser;
 use serde_json::{json, Value};
 use std::{
@@ -5,7 +6,7 @@ use std::{
     fs,
     io::{self, BufRead, BufReader, Read, Write},
     path::Path,
-    process::{Command, Stdio},
+    process::{ChildStderr, Command, ExitCode, ExitStatus, Stdio},
     thread,
 };
 
@@ -40,7 +41,9 @@ enum Cargo {
     long_about = "Runs a coverage tool (`cargo llvm-cov` or `cargo tarpaulin`) and outputs structured \
     JSON showing per-file uncovered line ranges and coverage summaries.\n\n\
     Use --input to parse an existing coverage JSON report instead of running a tool.\n\n\
-    All extra arguments are passed through to the underlying coverage tool."
+    All extra arguments are passed through to the underlying coverage tool.\n\n\
+    Exits 0 on success, 1 when tests fail under the coverage tool, 2 when the tool or cargo carpulin \
+    itself fails, 3 when line coverage is below --fail-under; --no-fail turns 1 and 3 into 0."
 )]
 struct Args {
     /// Parse from file or stdin ("-") instead of running a coverage tool
@@ -54,6 +57,22 @@ struct Args {
     /// Arguments passed through to the coverage tool
     #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
     cargo_args: Vec<String>,
+
+    /// Cap output size in characters, keeping the least-covered files (output becomes compact JSON)
+    #[arg(long, value_name = "N")]
+    max_chars: Option<usize>,
+
+    /// Cap output size in tokens (estimated as 4 characters each)
+    #[arg(long, value_name = "N")]
+    max_tokens: Option<usize>,
+
+    /// Exit 3 if total line coverage is below PERCENT
+    #[arg(long, value_name = "PERCENT")]
+    fail_under: Option<f64>,
+
+    /// Exit 0 even when tests fail or coverage is below --fail-under (tool failures still exit 2)
+    #[arg(long)]
+    no_fail: bool,
 }
 
 /// Groups sorted line numbers into compact range strings.
@@ -86,6 +105,34 @@ fn group_into_ranges(lines: &[i64]) -> Vec<String> {
     ranges
 }
 
+/// Trims `files` to fit `max_chars`, least-covered files first, and records how
+/// many were dropped under `omitted`. The summary is always kept.
+fn apply_budget(mut result: Value, max_chars: usize) -> Value {
+    let mut files = match result["files"].take() {
+        Value::Array(files) => files,
+        _ => return result,
+    };
+    files.sort_by(|a, b| {
+        let pa = a["coverage"]["lines"]["percent"].as_f64().unwrap_or(0.0);
+        let pb = b["coverage"]["lines"]["percent"].as_f64().unwrap_or(0.0);
+        pa.total_cmp(&pb)
+    });
+    let reserve = serde_json::to_string(&result["summary"])
+        .map(|s| s.len())
+        .unwrap_or(0)
+        + 40;
+    let items = files
+        .into_iter()
+        .map(|f| ("files".to_string(), f))
+        .collect();
+    let (kept, omitted) = fit_to_budget(items, max_chars.saturating_sub(reserve));
+    result["files"] = Value::Array(kept);
+    if !omitted.is_empty() {
+        result["omitted"] = omitted.to_json();
+    }
+    result
+}
+
 /// Parses `cargo llvm-cov --json` output into structured coverage JSON.
 ///
 /// Segments format: `[line, col, count, hasCount, isRegionEntry, isGap]`
@@ -258,16 +305,82 @@ fn parse_tarpaulin(json_str: &str) -> Result<Value, Box<dyn std::error::Error>>
     Ok(json!({ "summary": summary, "files": files }))
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// Whether a coverage tool's stderr line says the tests themselves failed,
+/// as opposed to the build or the tool.
+fn is_test_failure(line: &str) -> bool {
+    let line = line.trim_start();
+    // cargo test (under llvm-cov), and tarpaulin
+    line.starts_with("error: test failed") || line.contains("Test failed during run")
+}
+
+/// Streams a child's stderr to ours; returns whether any line reported failing tests.
+fn forward_stderr(stderr: ChildStderr) -> thread::JoinHandle<bool> {
+    thread::spawn(move || {
+        let mut tests_failed = false;
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            tests_failed |= is_test_failure(&line);
+            let _ = writeln!(io::stderr(), "{}", line);
+        }
+        tests_failed
+    })
+}
+
+/// The outcome of a coverage tool that exited unsuccessfully.
+fn tool_failure(tool: &str, status: ExitStatus, tests_failed: bool) -> Outcome {
+    let code = status.code().unwrap_or(-1);
+    if tests_failed {
+        eprintln!("✗ Tests failed under cargo {} (exit code {})", tool, code);
+        Outcome::Found
+    } else {
+        eprintln!("✗ cargo {} failed with exit code {}", tool, code);
+        Outcome::ToolFailure
+    }
+}
+
+fn main() -> ExitCode {
     let Cargo::Carpulin(args) = Cargo::parse();
+    let no_fail = args.no_fail;
+    match run(args) {
+        Ok(outcome) => outcome.exit_code(no_fail),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            Outcome::ToolFailure.exit_code(no_fail)
+        }
+    }
+}
+
+/// Parses a report of `tool` into the carpulin JSON.
+fn parse_report(tool: &str, json_str: &str) -> Result<Value, Box<dyn std::error::Error>> {
+    match tool {
+        "tarpaulin" => parse_tarpaulin(json_str),
+        _ => parse_llvm_cov(json_str),
+    }
+}
 
-    let json_str = match &args.input {
+/// Prints the parsed coverage, within the output budget; returns the total line coverage.
+fn print_coverage(args: &Args, result: Value) -> Result<f64, Box<dyn std::error::Error>> {
+    let files_count = result["files"].as_array().map(|a| a.len()).unwrap_or(0);
+    eprintln!("✓ Processed {} file(s), outputting JSON...", files_count);
+    let percent = result["summary"]["lines"]["percent"]
+        .as_f64()
+        .unwrap_or(0.0);
+    match char_budget(args.max_chars, args.max_tokens) {
+        Some(max) => println!("{}", serde_json::to_string(&apply_budget(result, max))?),
+        None => println!("{}", serde_json::to_string_pretty(&result)?),
+    }
+    Ok(percent)
+}
+
+/// Runs cargo carpulin and prints its output; returns the outcome that decides the exit code.
+fn run(args: Args) -> Result<Outcome, Box<dyn std::error::Error>> {
+    // The tool's exit status and whether its tests failed, when it didn't succeed
+    let (json_str, failure) = match &args.input {
         Some(p) if p == "-" => {
             let mut s = String::new();
             io::stdin().read_to_string(&mut s)?;
-            s
+            (s, None)
         }
-        Some(p) => fs::read_to_string(p)?,
+        Some(p) => (fs::read_to_string(p)?, None),
         None => {
             let (program, base_args): (&str, Vec<&str>) = match args.tool.as_str() {
                 "tarpaulin" => (
@@ -287,6 +400,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 // so we use a temp dir approach
                 let tmp = std::env::temp_dir().join("carpulin_tarpaulin");
                 let _ = fs::create_dir_all(&tmp);
+                // A report left by an earlier run mustn't pass for this one's
+                let report_path = tmp.join("tarpaulin-report.json");
+                let _ = fs::remove_file(&report_path);
                 let mut cmd = Command::new("cargo");
                 cmd.arg("tarpaulin")
                     .arg("--out")
@@ -298,56 +414,81 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     .stderr(Stdio::piped());
 
                 let mut child = cmd.spawn()?;
-                let stderr = child.stderr.take().expect("capture stderr");
-                let stderr_handle = thread::spawn(move || {
-                    for line in BufReader::new(stderr).lines().map_while(Result::ok) {
-                        let _ = writeln!(io::stderr(), "{}", line);
-                    }
-                });
-                let _ = child.wait()?;
-                let _ = stderr_handle.join();
-
-                let report_path = tmp.join("tarpaulin-report.json");
-                fs::read_to_string(&report_path)?
+                let stderr_handle = forward_stderr(child.stderr.take().expect("capture stderr"));
+                let status = child.wait()?;
+                let tests_failed = stderr_handle.join().unwrap_or(false);
+                if status.success() {
+                    (fs::read_to_string(&report_path)?, None)
+                } else {
+                    let report = fs::read_to_string(&report_path).unwrap_or_default();
+                    (report, Some((status, tests_failed)))
+                }
             } else {
                 // llvm-cov outputs JSON to stdout
                 let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
 
-                let stderr = child.stderr.take().expect("capture stderr");
-                let stderr_handle = thread::spawn(move || {
-                    for line in BufReader::new(stderr).lines().map_while(Result::ok) {
-                        let _ = writeln!(io::stderr(), "{}", line);
-                    }
-                });
+                let stderr_handle = forward_stderr(child.stderr.take().expect("capture stderr"));
                 let mut stdout_buf = String::new();
                 child
                     .stdout
                     .take()
                     .expect("capture stdout")
                     .read_to_string(&mut stdout_buf)?;
-                let _ = child.wait()?;
-                let _ = stderr_handle.join();
-                stdout_buf
+                let status = child.wait()?;
+                let tests_failed = stderr_handle.join().unwrap_or(false);
+                let failure = (!status.success()).then_some((status, tests_failed));
+                (stdout_buf, failure)
             }
         }
     };
 
-    eprintln!("⠿ Parsing coverage data...");
-    let result = match args.tool.as_str() {
-        "tarpaulin" => parse_tarpaulin(&json_str)?,
-        _ => parse_llvm_cov(&json_str)?,
-    };
+    if let Some((status, tests_failed)) = failure {
+        let outcome = tool_failure(&args.tool, status, tests_failed);
+        // Failing tests may still leave a report; print it if there is one
+        match parse_report(&args.tool, &json_str)
+            .ok()
+            .filter(|_| tests_failed)
+        {
+            Some(result) => {
+                print_coverage(&args, result)?;
+            }
+            None => {
+                let code = status.code().unwrap_or(-1);
+                let error = json!({
+                    "error": format!("cargo {} failed with exit code {}", args.tool, code),
+                    "exit_code": code,
+                });
+                println!("{}", serde_json::to_string(&error)?);
+            }
+        }
+        return Ok(outcome);
+    }
 
-    let files_count = result["files"].as_array().map(|a| a.len()).unwrap_or(0);
-    eprintln!("✓ Processed {} file(s), outputting JSON...", files_count);
-    println!("{}", serde_json::to_string_pretty(&result)?);
-    Ok(())
+    eprintln!("⠿ Parsing coverage data...");
+    let percent = print_coverage(&args, parse_report(&args.tool, &json_str)?)?;
+    match args.fail_under {
+        Some(min) if percent < min => {
+            eprintln!(
+                "✗ Line coverage {}% is below --fail-under {}%",
+                percent, min
+            );
+            Ok(Outcome::ThresholdViolation)
+        }
+        _ => Ok(Outcome::Clean),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_is_test_failure() {
+        assert!(is_test_failure("error: test failed, to rerun pass `--lib`"));
+        assert!(is_test_failure("Error: \"Test failed during run\""));
+        assert!(!is_test_failure("error: could not compile `foo`"));
+    }
+
     #[test]
     fn test_group_into_ranges_empty() {
         assert_eq!(group_into_ranges(&[]), Vec::<String>::new());
@@ -419,6 +560,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_apply_budget_keeps_least_covered_files() {
+        let files: Vec<Value> = [80.0, 12.5, 100.0, 40.0, 66.67, 5.0, 90.0, 33.0]
+            .iter()
+            .enumerate()
+            .map(|(i, percent)| {
+                json!({
+                    "file": format!("src/module_{}.rs", i),
+                    "coverage": { "lines": { "count": 100, "covered": 50, "percent": percent } },
+                    "uncovered_lines": ["10-12", "22", "30-31"]
+                })
+            })
+            .collect();
+        let result = json!({ "summary": { "lines": { "count": 800 } }, "files": files });
+
+        let budgeted = apply_budget(result, 600);
+        assert!(serde_json::to_string(&budgeted).unwrap().len() <= 600);
+        let kept = budgeted["files"].as_array().unwrap();
+        let percents: Vec<f64> = kept
+            .iter()
+            .map(|f| f["coverage"]["lines"]["percent"].as_f64().unwrap())
+            .collect();
+        assert_eq!(&percents[..2], &[5.0, 12.5]);
+        assert_eq!(
+            kept.len() + budgeted["omitted"]["omitted"].as_u64().unwrap() as usize,
+            8
+        );
+    }
+
     #[test]
     fn test_parse_tarpaulin_fixture() {
         let fixture =
@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Returns the path to the cargo-carpulin binary (debug build).
+fn cargo_carpulin_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_cargo-carpulin"))
+}
+
+/// Returns the path to the recorded llvm-cov report of tools/coverage-test.
+fn llvm_cov_fixture() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.pop(); // wrappers/
+    path.pop(); // project root
+    path.push("tools/coverage-test/fixtures/llvm-cov.json");
+    assert!(path.exists(), "fixture not found at {:?}", path);
+    path
+}
+
+fn exit_code(args: &[&str]) -> i32 {
+    Command::new(cargo_carpulin_bin())
+        .arg("carpulin")
+        .arg("--input")
+        .arg(llvm_cov_fixture())
+        .args(args)
+        .output()
+        .expect("failed to run cargo-carpulin")
+        .status
+        .code()
+        .expect("exit code")
+}
+
+#[test]
+fn report_exits_0() {
+    assert_eq!(exit_code(&[]), 0);
+    assert_eq!(exit_code(&["--fail-under", "0"]), 0);
+}
+
+#[test]
+fn coverage_below_threshold_exits_3() {
+    assert_eq!(exit_code(&["--fail-under", "100"]), 3);
+    assert_eq!(exit_code(&["--fail-under", "100", "--no-fail"]), 0);
+}
+
+#[test]
+fn unparsable_report_exits_2() {
+    let output = Command::new(cargo_carpulin_bin())
+        .args(["carpulin", "--no-fail", "--input", "does-not-exist.json"])
+        .output()
+        .expect("failed to run cargo-carpulin");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+/// Puts a fake `cargo` first on PATH: it prints `report` (if any) to stdout,
+/// `stderr` to stderr and exits 101. Returns the PATH to run carpulin with.
+#[cfg(unix)]
+fn failing_cargo(name: &str, report: Option<&std::path::Path>, stderr: &str) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("carpulin-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let cat = report.map(|p| format!("cat '{}'\n", p.display()));
+    let script = format!(
+        "#!/bin/sh\n{}echo '{}' >&2\nexit 101\n",
+        cat.unwrap_or_default(),
+        stderr
+    );
+    let cargo = dir.join("cargo");
+    std::fs::write(&cargo, script).unwrap();
+    std::fs::set_permissions(&cargo, std::fs::Permissions::from_mode(0o755)).unwrap();
+    format!(
+        "{}:{}",
+        dir.display(),
+        std::env::var("PATH").unwrap_or_default()
+    )
+}
+
+#[cfg(unix)]
+#[test]
+fn failing_tests_still_print_the_report() {
+    let path = failing_cargo(
+        "tests-failed",
+        Some(&llvm_cov_fixture()),
+        "error: test failed, to rerun pass `--lib`",
+    );
+    let output = Command::new(cargo_carpulin_bin())
+        .arg("carpulin")
+        .env("PATH", path)
+        .output()
+        .expect("failed to run cargo-carpulin");
+    let report: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("stdout should be JSON");
+    assert!(
+        report["summary"]["lines"]["percent"].is_number(),
+        "{}",
+        report
+    );
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[cfg(unix)]
+#[test]
+fn tool_failure_prints_a_json_error() {
+    let path = failing_cargo("tool-failed", None, "error: could not compile `demo`");
+    let output = Command::new(cargo_carpulin_bin())
+        .arg("carpulin")
+        .env("PATH", path)
+        .output()
+        .expect("failed to run cargo-carpulin");
+    let error: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("stdout should be JSON");
+    assert_eq!(error["exit_code"], 101);
+    assert_eq!(error["error"], "cargo llvm-cov failed with exit code 101");
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -1,8 +1,20 @@
-use clap::Parser;
+use cargo_chec_core::{
+    apply_edits, attach_context, char_budget, dedup_diagnostics, enclosing_items, file_uri,
+    fit_to_budget, git_changed_lines, group_by, parse_cargo_message, publish_diagnostics,
+    rank_by_root_cause, root_causes, select_fixes, summarize, to_checkstyle, to_codequality,
+    to_sarif, unified_diff, workspace_root, Annotation, Baseline, ChangedLines, Diagnostic, Level,
+    Outcome, Span,
+};
+use clap::{Parser, ValueEnum};
 use serde_json::{json, Value};
 use std::{
+    collections::HashSet,
     fs,
-    io::{self, Read},
+    io::{self, BufRead, BufReader, Read},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::{Command, ExitCode, ExitStatus, Stdio},
+    thread,
 };
 
 #[derive(Parser)]
@@ -18,7 +30,25 @@ enum Cargo {
     long_about = "Runs `cargo check --message-format=json` and transforms the output into a \
     simplified JSON array of error strings. Useful for CI/CD pipelines, editors, and AI tools.\n\n\
     All cargo check flags are supported and passed through (e.g. --release, --package, --all-targets).\n\n\
-    Use --input to parse existing cargo check output instead of running cargo check."
+    Use --input to parse existing cargo check output instead of running cargo check.\n\n\
+    Use --only-codes / --exclude-codes to filter by error code or lint name.\n\n\
+    Likely root causes (syntax errors, unresolved imports, the first error in each file, \
+    errors in upstream crates) are listed first; --root-causes-only drops the rest.\n\n\
+    Use --context N to include N source lines either side of each error, with the span marked ⟦ ⟧.\n\n\
+    Use --format compact for shorter strings built from rustc's message and help/note text.\n\n\
+    Use --format objects for structured diagnostics (level, code, file, line/column range, related, suggestions).\n\n\
+    Use --format github for GitHub Actions annotation lines (::error file=...,line=...::message).\n\n\
+    Use --format lsp for LSP publishDiagnostics params per file (0-based ranges, severity, relatedInformation).\n\n\
+    Use --format checkstyle (XML) or --format codequality (GitLab) for CI report files.\n\n\
+    Use --format sarif for a SARIF 2.1.0 log (rules per code, relatedLocations, fixes) for code-scanning dashboards.\n\n\
+    Use --fix-plan to get only the MachineApplicable edits as {file, line, byte_start, byte_end, replacement, applicability}.\n\n\
+    Use --changed-since REF to keep only diagnostics on lines changed since a git ref (--whole-files for whole changed files).\n\n\
+    Use --write-baseline FILE to record current issues, then --baseline FILE to report only new ones.\n\n\
+    Use --stream to print each diagnostic as a JSON line as soon as cargo emits it, then a summary line; \
+    --fail-fast N stops cargo after N errors.\n\n\
+    Use --apply to write those edits to the working tree (--dry-run for a unified diff instead).\n\n\
+    Exits 0 when nothing is reported, 1 when diagnostics are reported, 2 when cargo check or cargo chec \
+    itself fails; --no-fail turns 1 into 0."
 )]
 struct Args {
     /// Parse from file or stdin ("-") instead of running cargo check
@@ -32,78 +62,698 @@ struct Args {
     /// Include warnings in the output
     #[arg(long)]
     include_warnings: bool,
+
+    /// Only report diagnostics with these error codes or lint names (e.g. E0308,unused_variables);
+    /// a lint named here is reported without --include-warnings
+    #[arg(long, value_name = "CODES", value_delimiter = ',')]
+    only_codes: Vec<String>,
+
+    /// Drop diagnostics with these error codes or lint names
+    #[arg(long, value_name = "CODES", value_delimiter = ',')]
+    exclude_codes: Vec<String>,
+
+    /// Keep repeated diagnostics (same code, span and message) instead of merging them
+    #[arg(long)]
+    no_dedup: bool,
+
+    /// Keep rustc's order instead of ranking likely root causes first
+    #[arg(long)]
+    no_rank: bool,
+
+    /// Only report likely root causes: syntax errors, unresolved imports, first error per file
+    #[arg(long, conflicts_with_all = ["no_rank", "fix_plan", "apply"])]
+    root_causes_only: bool,
+
+    /// Output format
+    #[arg(long, value_enum, default_value = "strings")]
+    format: Format,
+
+    /// Nest diagnostics under their file, code or crate, after a summary object
+    #[arg(long, value_enum, value_name = "KEY")]
+    group_by: Option<GroupBy>,
+
+    /// Include N lines of source either side of each diagnostic, span marked with ⟦ ⟧
+    #[arg(long, value_name = "N")]
+    context: Option<usize>,
+
+    /// Cap output size in characters, keeping errors before warnings and appending an omitted marker
+    #[arg(long, value_name = "N")]
+    max_chars: Option<usize>,
+
+    /// Cap output size in tokens (estimated as 4 characters each)
+    #[arg(long, value_name = "N")]
+    max_tokens: Option<usize>,
+
+    /// Print each diagnostic as a JSON line as soon as cargo emits it, then a summary line
+    #[arg(long, conflicts_with_all = [
+        "group_by", "max_chars", "max_tokens", "root_causes_only",
+        "fix_plan", "apply", "write_baseline", "baseline",
+    ])]
+    stream: bool,
+
+    /// Stop cargo check after N errors and report what was collected so far
+    #[arg(long, value_name = "N")]
+    fail_fast: Option<NonZeroUsize>,
+
+    /// Output only MachineApplicable fix suggestions as a patch list (implies --include-warnings)
+    #[arg(long)]
+    fix_plan: bool,
+
+    /// Apply MachineApplicable fix suggestions to the working tree and output a JSON summary
+    #[arg(long, conflicts_with = "fix_plan")]
+    apply: bool,
+
+    /// With --apply, also apply MaybeIncorrect suggestions
+    #[arg(long, requires = "apply")]
+    allow_maybe_incorrect: bool,
+
+    /// With --apply, don't write files; include a unified diff in the summary instead
+    #[arg(long, requires = "apply")]
+    dry_run: bool,
+
+    /// Only report diagnostics whose primary or related spans touch lines changed since REF (git diff REF)
+    #[arg(long, value_name = "REF")]
+    changed_since: Option<String>,
+
+    /// With --changed-since, keep every diagnostic in a changed file, not just on changed lines
+    #[arg(long, requires = "changed_since")]
+    whole_files: bool,
+
+    /// Record the diagnostics that would be reported as known issues in FILE, then exit
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["baseline", "fix_plan", "apply"])]
+    write_baseline: Option<PathBuf>,
+
+    /// Report only diagnostics not recorded in FILE by --write-baseline; exit 1 if there are any
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["fix_plan", "apply"])]
+    baseline: Option<PathBuf>,
+
+    /// Exit 0 even when diagnostics are reported (tool failures still exit 2)
+    #[arg(long)]
+    no_fail: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Compact one-line strings
+    Strings,
+    /// Shorter strings from rustc's message and help/note children, without the rendered excerpt
+    Compact,
+    /// Objects with level, code, file, line/column range, message and related[]
+    Objects,
+    /// A SARIF 2.1.0 log for code-scanning tools (ignores --group-by and the output budget)
+    Sarif,
+    /// GitHub Actions `::error file=...` annotation lines instead of JSON (ignores --group-by and the output budget)
+    Github,
+    /// LSP `textDocument/publishDiagnostics` params, one per file (ignores --group-by and the output budget)
+    Lsp,
+    /// Checkstyle XML report for Jenkins and similar (ignores --group-by and the output budget)
+    Checkstyle,
+    /// GitLab Code Quality JSON report (ignores --group-by and the output budget)
+    Codequality,
+}
+
+impl Format {
+    /// One diagnostic as a JSON string or object; whole-report formats use objects.
+    fn render(self, d: &Diagnostic) -> Value {
+        match self {
+            Format::Strings => json!(d.to_string()),
+            Format::Compact => json!(d.compact()),
+            _ => d.to_json(),
+        }
+    }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum GroupBy {
+    File,
+    Code,
+    Crate,
+}
+
+impl GroupBy {
+    fn name(self) -> &'static str {
+        match self {
+            GroupBy::File => "file",
+            GroupBy::Code => "code",
+            GroupBy::Crate => "crate",
+        }
+    }
+
+    fn key(self, d: &Diagnostic) -> String {
+        match self {
+            GroupBy::File => d.span.file.clone(),
+            GroupBy::Code => d.code.clone().unwrap_or_else(|| "(none)".to_string()),
+            GroupBy::Crate => d.package.clone().unwrap_or_else(|| "(unknown)".to_string()),
+        }
+    }
+}
+
+/// rustc reports paths relative to the workspace root, which may be an ancestor
+/// of the current directory; look upwards until the file is found.
+fn resolve_source_path(file: &str) -> PathBuf {
+    let path = Path::new(file);
+    if path.is_absolute() || path.exists() {
+        return path.to_path_buf();
+    }
+    std::env::current_dir()
+        .ok()
+        .and_then(|cwd| cwd.ancestors().map(|a| a.join(file)).find(|p| p.exists()))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// `file://` URI of a path reported by rustc, for LSP output.
+fn document_uri(file: &str) -> String {
+    let path = resolve_source_path(file);
+    let absolute = fs::canonicalize(&path)
+        .or_else(|_| std::path::absolute(&path))
+        .unwrap_or(path);
+    file_uri(&absolute)
+}
+
+/// Applies (or with `dry_run`, diffs) the selected fix suggestions file by file
+/// and returns a JSON summary of edits, files touched, overlaps and errors.
+fn apply_fixes(diagnostics: &[Diagnostic], allow_maybe_incorrect: bool, dry_run: bool) -> Value {
+    let selection = select_fixes(diagnostics, allow_maybe_incorrect);
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    let mut diff = String::new();
+    let mut total = 0;
+
+    for (file, edits) in selection.edits_by_file() {
+        let path = resolve_source_path(&file);
+        let result = fs::read_to_string(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+            .and_then(|src| {
+                if dry_run {
+                    unified_diff(&file, &src, &edits).map(|d| diff.push_str(&d))
+                } else {
+                    apply_edits(&src, &edits)
+                        .and_then(|new| fs::write(&path, new).map_err(|e| e.to_string()))
+                }
+            });
+        match result {
+            Ok(()) => {
+                total += edits.len();
+                files.push(json!({ "file": file, "edits": edits.len() }));
+            }
+            Err(e) => errors.push(json!({ "file": file, "error": e })),
+        }
+    }
+
+    let skipped: Vec<Value> = selection
+        .conflicts
+        .iter()
+        .map(|s| json!({ "file": s.edits[0].file, "line": s.edits[0].line_start, "message": s.message }))
+        .collect();
+
+    let mut summary = json!({
+        "dry_run": dry_run,
+        "edits": total,
+        "files": files,
+        "skipped_overlapping": skipped,
+        "errors": errors,
+    });
+    if dry_run {
+        summary["diff"] = json!(diff);
+    }
+    summary
+}
+
+fn main() -> ExitCode {
     let Cargo::Chec(args) = Cargo::parse();
+    let no_fail = args.no_fail;
+    match run(args) {
+        Ok(outcome) => outcome.exit_code(no_fail),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            Outcome::ToolFailure.exit_code(no_fail)
+        }
+    }
+}
+
+/// Filters diagnostics one at a time as cargo emits them, so they can be
+/// streamed and counted for --fail-fast before the build finishes.
+struct Intake<'a> {
+    args: &'a Args,
+    changed: Option<(ChangedLines, PathBuf)>,
+    diagnostics: Vec<Diagnostic>,
+    /// Whether cargo reported any error, before the level and code filters
+    saw_errors: bool,
+    /// Diagnostics that passed the level and code filters, before --changed-since
+    considered: usize,
+    /// Code, span and message of each diagnostic kept so far, to stream and count repeats once
+    seen: HashSet<(Option<String>, Span, String)>,
+    errors: usize,
+}
 
-    let (json_str, failure_opt) = match &args.input {
+impl Intake<'_> {
+    /// Takes one line of cargo's output; returns false once --fail-fast's limit is reached.
+    fn line(&mut self, line: &str) -> bool {
+        let args = self.args;
+        let Some(mut d) = serde_json::from_str::<Value>(line)
+            .ok()
+            .and_then(|log| parse_cargo_message(&log))
+        else {
+            return true;
+        };
+        self.saw_errors |= d.level == Level::Error;
+        let code = d.code.as_deref().unwrap_or("");
+        let only = args.only_codes.iter().any(|c| c == code);
+        // Asking for a lint by name is asking for its warnings
+        let keep = match d.level {
+            Level::Error => true,
+            Level::Warning => args.include_warnings || args.fix_plan || args.apply || only,
+            _ => false,
+        } && (args.only_codes.is_empty() || only)
+            && !args.exclude_codes.iter().any(|c| c == code);
+        if !keep {
+            return true;
+        }
+        self.considered += 1;
+        if let Some((changed, root)) = &self.changed {
+            if !changed.touches_diagnostic(&d, root, args.whole_files) {
+                return true;
+            }
+        }
+        if self
+            .seen
+            .insert((d.code.clone(), d.span.clone(), d.message.clone()))
+        {
+            if args.stream {
+                if let Some(n) = args.context {
+                    attach_context(std::slice::from_mut(&mut d), n, |file| {
+                        fs::read_to_string(resolve_source_path(file)).ok()
+                    });
+                }
+                match args.format {
+                    Format::Github => println!("{}", Annotation::from_diagnostic(&d)),
+                    format => println!("{}", format.render(&d)),
+                }
+            }
+            if d.level == Level::Error {
+                self.errors += 1;
+            }
+        }
+        self.diagnostics.push(d);
+        args.fail_fast.is_none_or(|n| self.errors < n.get())
+    }
+}
+
+/// The message reported when cargo check itself fails.
+fn cargo_failure(status: &ExitStatus, stderr: &[u8]) -> String {
+    format!(
+        "Cargo check failed with exit code {}: {}",
+        status.code().unwrap_or(-1),
+        String::from_utf8_lossy(stderr)
+    )
+}
+
+/// Runs cargo chec and prints its output; returns the outcome that decides the exit code.
+fn run(args: Args) -> Result<Outcome, Box<dyn std::error::Error>> {
+    if args.stream
+        && !matches!(
+            args.format,
+            Format::Strings | Format::Compact | Format::Objects | Format::Github
+        )
+    {
+        return Err("--stream supports --format strings, compact, objects or github".into());
+    }
+    let changed = match &args.changed_since {
+        Some(git_ref) => Some((
+            git_changed_lines(git_ref)?,
+            workspace_root().or_else(|_| std::env::current_dir())?,
+        )),
+        None => None,
+    };
+    let mut intake = Intake {
+        args: &args,
+        changed,
+        diagnostics: Vec::new(),
+        saw_errors: false,
+        considered: 0,
+        seen: HashSet::new(),
+        errors: 0,
+    };
+    let mut stopped = false;
+
+    let failure_opt = match &args.input {
         Some(p) if p == "-" => {
             eprintln!("⠿ Reading from stdin...");
-            let mut s = String::new();
-            io::stdin().read_to_string(&mut s)?;
-            (s, None)
+            for line in io::stdin().lock().lines() {
+                if !intake.line(&line?) {
+                    stopped = true;
+                    break;
+                }
+            }
+            None
         }
         Some(p) => {
             eprintln!("⠿ Reading from file: {}", p);
-            (fs::read_to_string(p)?, None)
+            for line in BufReader::new(fs::File::open(p)?).lines() {
+                if !intake.line(&line?) {
+                    stopped = true;
+                    break;
+                }
+            }
+            None
         }
         None => {
             eprintln!("⠿ Running cargo check...");
-            let output = std::process::Command::new("cargo")
+            let mut child = Command::new("cargo")
                 .arg("check")
                 .arg("--message-format=json")
                 .args(&args.cargo_args)
-                .output()?;
-            let status = output.status;
-            let stderr = output.stderr;
-            (String::from_utf8(output.stdout)?, Some((status, stderr)))
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            let mut stderr = child.stderr.take().expect("capture stderr");
+            let stderr_handle = thread::spawn(move || {
+                let mut captured = Vec::new();
+                let _ = stderr.read_to_end(&mut captured);
+                captured
+            });
+            for line in BufReader::new(child.stdout.take().expect("capture stdout")).lines() {
+                if !intake.line(&line?) {
+                    stopped = true;
+                    break;
+                }
+            }
+            if stopped {
+                // The build's exit status and stderr no longer say anything
+                let _ = child.kill();
+                let _ = child.wait();
+                None
+            } else {
+                let status = child.wait()?;
+                Some((status, stderr_handle.join().unwrap_or_default()))
+            }
+        }
+    };
+    if stopped {
+        eprintln!("⠿ Stopped after {} error(s) (--fail-fast)", intake.errors);
+    }
+    // cargo exits non-zero on compile errors too; its failure is explained by
+    // them, whether or not the filters keep any
+    let has_errors = intake.saw_errors;
+
+    let mut diagnostics = intake.diagnostics;
+    if let Some(git_ref) = &args.changed_since {
+        eprintln!(
+            "⠿ Kept {} of {} diagnostic(s) touching changes since {}",
+            diagnostics.len(),
+            intake.considered,
+            git_ref
+        );
+    }
+    // Baselines count every occurrence, so --no-dedup doesn't change what they match
+    if args.write_baseline.is_some() || args.baseline.is_some() {
+        let items = enclosing_items(&diagnostics, |file| {
+            fs::read_to_string(resolve_source_path(file)).ok()
+        });
+        if let Some(path) = &args.write_baseline {
+            // A build that stopped before its diagnostics would record too few issues
+            if let Some((status, stderr)) = failure_opt
+                .as_ref()
+                .filter(|(status, _)| !status.success() && !has_errors)
+            {
+                eprintln!("✗ Not writing {}: cargo check failed", path.display());
+                let summary = json!({
+                    "baseline": path,
+                    "cargo_failure": cargo_failure(status, stderr),
+                });
+                println!("{}", serde_json::to_string(&summary)?);
+                return Ok(Outcome::ToolFailure);
+            }
+            let baseline = Baseline::from_diagnostics(&diagnostics, &items);
+            fs::write(
+                path,
+                serde_json::to_string_pretty(&baseline.to_json())? + "\n",
+            )?;
+            eprintln!(
+                "✓ Wrote {} known issue(s) to {}",
+                baseline.len(),
+                path.display()
+            );
+            let summary = json!({
+                "baseline": path,
+                "issues": baseline.len(),
+                "fingerprints": baseline.entries.len(),
+            });
+            println!("{}", serde_json::to_string(&summary)?);
+            return Ok(Outcome::Clean);
+        }
+        if let Some(path) = &args.baseline {
+            let file: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+            let baseline =
+                Baseline::from_json(&file).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let (new, suppressed) = baseline.new_diagnostics(diagnostics, &items);
+            eprintln!("⠿ Baseline suppressed {} known issue(s)", suppressed);
+            diagnostics = new;
+        }
+    }
+    if !args.no_dedup {
+        diagnostics = dedup_diagnostics(diagnostics);
+    }
+    if args.stream {
+        let failure = failure_opt
+            .filter(|(status, _)| !status.success() && !has_errors)
+            .map(|(status, stderr)| cargo_failure(&status, &stderr));
+        if args.format == Format::Github {
+            if let Some(failure) = &failure {
+                let title = Some("cargo check".to_string());
+                println!("{}", Annotation::new("error", title, failure.clone()));
+            }
+        } else {
+            let mut summary = summarize(&diagnostics);
+            if let Some(failure) = &failure {
+                summary["cargo_failure"] = json!(failure);
+            }
+            if stopped {
+                summary["stopped_after_errors"] = json!(intake.errors);
+            }
+            println!("{}", json!({ "summary": summary }));
         }
+        eprintln!("✓ Streamed {} issue(s)", diagnostics.len());
+        return Ok(match failure {
+            Some(_) => Outcome::ToolFailure,
+            None if diagnostics.is_empty() => Outcome::Clean,
+            None => Outcome::Found,
+        });
+    }
+    if args.root_causes_only {
+        diagnostics = root_causes(diagnostics);
+    } else if !args.no_rank {
+        diagnostics = rank_by_root_cause(diagnostics);
+    }
+
+    // Only a failure that produced no errors means the check itself didn't run to completion
+    let outcome = match &failure_opt {
+        Some((status, _)) if !status.success() && !has_errors => Outcome::ToolFailure,
+        _ if diagnostics.is_empty() => Outcome::Clean,
+        _ => Outcome::Found,
     };
 
-    eprintln!("⠿ Parsing compiler messages...");
-    let mut results: Vec<String> = json_str.lines()
-        .filter_map(|l| serde_json::from_str::<Value>(l).ok())
-        .filter_map(|log| {
-            let msg = log.get("message").filter(|_| log["reason"] == "compiler-message")?;
-            let (severity, label) = match msg["level"].as_str()? {
-                "error" => (5, "Error"),
-                "warning" if args.include_warnings => (4, "Warning"),
-                _ => return None
+    if args.fix_plan {
+        let selection = select_fixes(&diagnostics, false);
+        let plan = selection.fix_plan();
+        for s in &selection.conflicts {
+            eprintln!(
+                "⠿ Skipped a fix overlapping another one at {}:{}: {}",
+                s.edits[0].file, s.edits[0].line_start, s.message
+            );
+        }
+        eprintln!(
+            "✓ Found {} machine-applicable edit(s), outputting JSON...",
+            plan.len()
+        );
+        println!("{}", serde_json::to_string(&plan)?);
+        return Ok(outcome);
+    }
+
+    if args.apply {
+        let summary = apply_fixes(&diagnostics, args.allow_maybe_incorrect, args.dry_run);
+        eprintln!(
+            "✓ {} {} edit(s), outputting JSON...",
+            if args.dry_run { "Planned" } else { "Applied" },
+            summary["edits"]
+        );
+        println!("{}", serde_json::to_string(&summary)?);
+        if summary["errors"].as_array().is_some_and(|e| !e.is_empty()) {
+            return Ok(Outcome::ToolFailure);
+        }
+        return Ok(outcome);
+    }
+
+    if let Some(n) = args.context {
+        attach_context(&mut diagnostics, n, |file| {
+            fs::read_to_string(resolve_source_path(file)).ok()
+        });
+    }
+
+    if args.format == Format::Sarif {
+        let mut log = to_sarif(&diagnostics, "cargo-chec", env!("CARGO_PKG_VERSION"));
+        if let Some((status, stderr)) = &failure_opt {
+            let mut invocation = json!({
+                "executionSuccessful": status.success() || has_errors,
+                "exitCode": status.code().unwrap_or(-1),
+            });
+            if outcome == Outcome::ToolFailure {
+                invocation["toolExecutionNotifications"] = json!([{
+                    "level": "error",
+                    "message": { "text": String::from_utf8_lossy(stderr) },
+                }]);
+            }
+            log["runs"][0]["invocations"] = json!([invocation]);
+        }
+        eprintln!(
+            "✓ Found {} issue(s), outputting SARIF...",
+            diagnostics.len()
+        );
+        println!("{}", serde_json::to_string(&log)?);
+        return Ok(outcome);
+    }
+
+    if args.format == Format::Checkstyle {
+        eprintln!(
+            "✓ Found {} issue(s), outputting Checkstyle XML...",
+            diagnostics.len()
+        );
+        print!("{}", to_checkstyle(&diagnostics));
+        return Ok(outcome);
+    }
+
+    if args.format == Format::Codequality {
+        eprintln!(
+            "✓ Found {} issue(s), outputting Code Quality JSON...",
+            diagnostics.len()
+        );
+        let items = enclosing_items(&diagnostics, |file| {
+            fs::read_to_string(resolve_source_path(file)).ok()
+        });
+        let report = to_codequality(&diagnostics, &items);
+        println!("{}", serde_json::to_string(&report)?);
+        return Ok(outcome);
+    }
+
+    if args.format == Format::Lsp {
+        let params = publish_diagnostics(&diagnostics, document_uri);
+        eprintln!(
+            "✓ Found {} issue(s) in {} file(s), outputting JSON...",
+            diagnostics.len(),
+            params.len()
+        );
+        println!("{}", serde_json::to_string(&params)?);
+        return Ok(outcome);
+    }
+
+    if args.format == Format::Github {
+        for d in &diagnostics {
+            println!("{}", Annotation::from_diagnostic(d));
+        }
+        if let Some((status, stderr)) = failure_opt.filter(|(status, _)| !status.success()) {
+            // Compile errors already have their own annotations
+            if !has_errors {
+                let title = Some("cargo check".to_string());
+                let message = cargo_failure(&status, &stderr);
+                println!("{}", Annotation::new("error", title, message));
+            }
+        }
+        eprintln!(
+            "✓ Found {} issue(s), outputting annotations...",
+            diagnostics.len()
+        );
+        return Ok(outcome);
+    }
+
+    // Listed next to the errors that caused it, but not on its own when the
+    // filters dropped them all
+    let kept_errors = diagnostics.iter().any(|d| d.level == Level::Error);
+    let failure = failure_opt
+        .filter(|(status, _)| !status.success() && (kept_errors || !has_errors))
+        .map(|(status, stderr)| cargo_failure(&status, &stderr));
+
+    let budget = char_budget(args.max_chars, args.max_tokens);
+    if budget.is_some() {
+        // Errors first; the sort is stable, so root causes (or, with --no-rank,
+        // first-occurring diagnostics) stay ahead of the cascading ones
+        diagnostics.sort_by_key(|d| d.level);
+    }
+    let items: Vec<(String, Value)> = diagnostics
+        .iter()
+        .map(|d| (d.level.as_str().to_string(), args.format.render(d)))
+        .collect();
+
+    let results: Vec<Value> = match args.group_by {
+        Some(by) => {
+            let mut summary = summarize(&diagnostics);
+            if let Some(failure) = failure {
+                summary["cargo_failure"] = json!(failure);
+            }
+            let rendered = match budget {
+                Some(max) => {
+                    // Room for the summary and every group's wrapper object
+                    let wrappers: usize = group_by(&diagnostics, |d| by.key(d))
+                        .iter()
+                        .map(|(key, group)| {
+                            json!({ by.name(): key, "count": group.len(), "diagnostics": [] })
+                                .to_string()
+                                .len()
+                                + 1
+                        })
+                        .sum();
+                    let reserve = serde_json::to_string(&summary)?.len() + 16 + wrappers;
+                    let (kept, omitted) = fit_to_budget(items, max.saturating_sub(reserve));
+                    if !omitted.is_empty() {
+                        summary["omitted"] = omitted.to_json();
+                    }
+                    kept
+                }
+                None => items.into_iter().map(|(_, v)| v).collect(),
             };
-            let span = msg["spans"].as_array()?.first()?;
-            let resource = span["file_name"].as_str()?;
-            let (sl, sc, ec) = (span["line_start"].as_i64()?, span["column_start"].as_i64()?, span["column_end"].as_i64()?);
-            let message: String = msg["rendered"].as_str()?.split_whitespace().collect::<Vec<_>>().join(" ");
-
-            let related: Vec<Value> = msg["children"].as_array().unwrap_or(&vec![]).iter().filter_map(|c| {
-                let sp = c["spans"].as_array()?.first()?;
-                Some(json!({"message": c["message"].as_str()?, "resource": sp["file_name"].as_str()?,
-                    "startLineNumber": sp["line_start"], "startColumn": sp["column_start"],
-                    "endLineNumber": sp["line_end"], "endColumn": sp["column_end"]}))
-            }).collect();
-
-            let mut out = format!("{} (severity {}) in {}:{}:{}-{}: {}",
-                label, severity, resource, sl, sc, ec, message);
-            for r in &related {
-                out.push_str(&format!(" Related: In {}:{}:{}-{}: {}",
-                    r["resource"].as_str().unwrap_or(""), r["startLineNumber"], r["startColumn"],
-                    r["endColumn"], r["message"].as_str().unwrap_or("").split_whitespace().collect::<Vec<_>>().join(" ")));
-            }
-            Some(out)
-        }).collect();
-
-    if let Some((status, stderr)) = failure_opt {
-        if !status.success() {
-            results.push(format!(
-                "Cargo check failed with exit code {}: {}",
-                status.code().unwrap_or(-1),
-                String::from_utf8_lossy(&stderr)
-            ));
-        }
-    }
-
-    eprintln!("✓ Found {} issue(s), outputting JSON...", results.len());
+            let pairs: Vec<(&Diagnostic, Value)> = diagnostics.iter().zip(rendered).collect();
+            let groups = group_by(&pairs, |(d, _)| by.key(d));
+            let mut results = vec![json!({ "summary": summary })];
+            results.extend(groups.into_iter().map(|(key, group)| {
+                json!({
+                    by.name(): key,
+                    "count": group.len(),
+                    "diagnostics": group.into_iter().map(|(_, v)| v.clone()).collect::<Vec<_>>(),
+                })
+            }));
+            results
+        }
+        None => {
+            let mut items = items;
+            if let Some(failure) = failure {
+                items.push((
+                    "cargo failure".to_string(),
+                    match args.format {
+                        Format::Strings | Format::Compact => json!(failure),
+                        _ => {
+                            json!({ "level": "error", "message": failure })
+                        }
+                    },
+                ));
+            }
+            match budget {
+                Some(max) => {
+                    let (mut kept, omitted) = fit_to_budget(items, max);
+                    if !omitted.is_empty() {
+                        kept.push(match args.format {
+                            Format::Strings | Format::Compact => {
+                                json!(omitted.to_string())
+                            }
+                            _ => omitted.to_json(),
+                        });
+                    }
+                    kept
+                }
+                None => items.into_iter().map(|(_, v)| v).collect(),
+            }
+        }
+    };
+
+    eprintln!("✓ Found {} issue(s), outputting JSON...", diagnostics.len());
     println!("{}", serde_json::to_string(&results)?);
-    Ok(())
+    Ok(outcome)
 }
@@ -0,0 +1,186 @@
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Returns the path to the cargo-chec binary (debug build).
+fn cargo_chec_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_cargo-chec"))
+}
+
+/// Returns the path to the recorded `cargo check` JSON output of tools/broken-tests.
+fn cargo_check_fixture() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.pop(); // wrappers/
+    path.pop(); // project root
+    path.push("tools/broken-tests/fixtures/cargo-check.json");
+    assert!(path.exists(), "fixture not found at {:?}", path);
+    path
+}
+
+/// Returns the path to the tools/broken-tests crate, which fails to compile.
+fn broken_tests_dir() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.pop(); // wrappers/
+    path.pop(); // project root
+    path.push("tools/broken-tests");
+    assert!(path.exists(), "broken-tests crate not found at {:?}", path);
+    path
+}
+
+/// Returns the path to the tools/build-fail crate, whose build script panics.
+fn build_fail_dir() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.pop(); // wrappers/
+    path.pop(); // project root
+    path.push("tools/build-fail");
+    assert!(path.exists(), "build-fail crate not found at {:?}", path);
+    path
+}
+
+/// Runs cargo chec on tools/broken-tests; returns its exit code and stdout.
+fn check_broken_tests(args: &[&str]) -> (i32, String) {
+    check_crate(broken_tests_dir(), args)
+}
+
+/// Runs cargo chec in `dir`; returns its exit code and stdout.
+fn check_crate(dir: PathBuf, args: &[&str]) -> (i32, String) {
+    let output = Command::new(cargo_chec_bin())
+        .arg("chec")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .expect("failed to run cargo-chec");
+    (
+        output.status.code().expect("exit code"),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    )
+}
+
+fn exit_code(args: &[&str]) -> i32 {
+    Command::new(cargo_chec_bin())
+        .arg("chec")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .expect("failed to run cargo-chec")
+        .status
+        .code()
+        .expect("exit code")
+}
+
+#[test]
+fn errors_exit_1() {
+    let fixture = cargo_check_fixture();
+    assert_eq!(exit_code(&["--input", fixture.to_str().unwrap()]), 1);
+}
+
+#[test]
+fn no_fail_exits_0_with_errors() {
+    let fixture = cargo_check_fixture();
+    assert_eq!(
+        exit_code(&["--no-fail", "--input", fixture.to_str().unwrap()]),
+        0
+    );
+}
+
+#[test]
+fn clean_run_exits_0() {
+    assert_eq!(exit_code(&["--input", "-"]), 0);
+}
+
+#[test]
+fn unreadable_input_exits_2() {
+    assert_eq!(
+        exit_code(&["--no-fail", "--input", "does-not-exist.json"]),
+        2
+    );
+}
+
+#[test]
+fn errors_dropped_by_code_filters_are_not_a_cargo_failure() {
+    // Only warnings are left: issues found, but cargo check itself ran fine
+    let (code, stdout) =
+        check_broken_tests(&["--only-codes", "unused_variables", "--include-warnings"]);
+    assert!(!stdout.contains("Cargo check failed"), "{}", stdout);
+    assert_eq!(code, 1, "{}", stdout);
+
+    let (code, stdout) = check_broken_tests(&["--only-codes", "E0308", "--exclude-codes", "E0308"]);
+    assert_eq!(stdout.trim(), "[]");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn streamed_errors_dropped_by_code_filters_are_not_a_cargo_failure() {
+    let (code, stdout) = check_broken_tests(&[
+        "--stream",
+        "--exclude-codes",
+        "E0308",
+        "--only-codes",
+        "E0308",
+    ]);
+    assert!(!stdout.contains("cargo_failure"), "{}", stdout);
+    assert_eq!(code, 0, "{}", stdout);
+}
+
+#[test]
+fn write_baseline_after_a_failed_build_exits_2_without_writing() {
+    let path = std::env::temp_dir().join(format!("chec-baseline-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let (code, stdout) = check_crate(
+        build_fail_dir(),
+        &["--write-baseline", path.to_str().unwrap()],
+    );
+    assert!(stdout.contains("cargo_failure"), "{}", stdout);
+    assert_eq!(code, 2, "{}", stdout);
+    assert!(!path.exists(), "baseline written after a failed build");
+}
+
+#[test]
+fn lints_named_in_only_codes_need_no_include_warnings() {
+    let fixture = cargo_check_fixture();
+    let output = Command::new(cargo_chec_bin())
+        .args(["chec", "--only-codes", "unused_variables", "--input"])
+        .arg(fixture)
+        .output()
+        .expect("failed to run cargo-chec");
+    let parsed: Vec<String> =
+        serde_json::from_slice(&output.stdout).expect("stdout should be a JSON array");
+    assert!(!parsed.is_empty());
+    assert!(
+        parsed
+            .iter()
+            .all(|s| s.starts_with("Warning[unused_variables]")),
+        "{:?}",
+        parsed
+    );
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn root_causes_only_rejects_fixes() {
+    for fix in ["--fix-plan", "--apply"] {
+        assert_eq!(exit_code(&["--root-causes-only", fix, "--input", "-"]), 2);
+    }
+}
+
+#[test]
+fn baseline_matches_with_or_without_dedup() {
+    // Every diagnostic twice, as for a lib and its lib-test target
+    let tmp = std::env::temp_dir();
+    let doubled = tmp.join(format!("chec-doubled-{}.json", std::process::id()));
+    let recorded = std::fs::read_to_string(cargo_check_fixture()).unwrap();
+    std::fs::write(&doubled, recorded.repeat(2)).unwrap();
+    let path = tmp.join(format!("chec-dedup-baseline-{}.json", std::process::id()));
+    let input = ["--include-warnings", "--input", doubled.to_str().unwrap()];
+
+    let baseline = path.to_str().unwrap();
+    let no_dedup: &[&str] = &["--no-dedup"];
+    for (write, read) in [(no_dedup, &[][..]), (&[], no_dedup)] {
+        let write_args = [&input[..], &["--write-baseline", baseline], write].concat();
+        assert_eq!(exit_code(&write_args), 0);
+        let read_args = [&input[..], &["--baseline", baseline], read].concat();
+        assert_eq!(exit_code(&read_args), 0, "{:?} then {:?}", write, read);
+    }
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&doubled);
+}
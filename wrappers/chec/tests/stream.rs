@@ -0,0 +1,61 @@
+use serde_json::Value;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Returns the path to the cargo-chec binary (debug build).
+fn cargo_chec_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_cargo-chec"))
+}
+
+/// Returns the path to the recorded `cargo check` JSON output of tools/broken-tests.
+fn cargo_check_fixture() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.pop(); // wrappers/
+    path.pop(); // project root
+    path.push("tools/broken-tests/fixtures/cargo-check.json");
+    assert!(path.exists(), "fixture not found at {:?}", path);
+    path
+}
+
+fn stream_lines(args: &[&str]) -> Vec<Value> {
+    let output = Command::new(cargo_chec_bin())
+        .args(["chec", "--stream", "--input"])
+        .arg(cargo_check_fixture())
+        .args(args)
+        .output()
+        .expect("failed to run cargo-chec");
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|l| serde_json::from_str(l).expect("each line should be JSON"))
+        .collect()
+}
+
+#[test]
+fn stream_prints_one_line_per_diagnostic_then_summary() {
+    let lines = stream_lines(&["--format", "objects"]);
+    let (summary, diagnostics) = lines.split_last().unwrap();
+    assert_eq!(diagnostics.len(), 13);
+    assert!(diagnostics.iter().all(|d| d["level"] == "error"));
+    assert_eq!(summary["summary"]["errors"], 13);
+    assert!(summary["summary"].get("stopped_after_errors").is_none());
+}
+
+#[test]
+fn fail_fast_stops_after_n_errors() {
+    let lines = stream_lines(&["--fail-fast", "2"]);
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].as_str().unwrap().starts_with("Error[E0432]"));
+    assert_eq!(lines[2]["summary"]["errors"], 2);
+    assert_eq!(lines[2]["summary"]["stopped_after_errors"], 2);
+}
+
+#[test]
+fn stream_rejects_whole_report_formats() {
+    let output = Command::new(cargo_chec_bin())
+        .args(["chec", "--stream", "--format", "sarif", "--input"])
+        .arg(cargo_check_fixture())
+        .output()
+        .expect("failed to run cargo-chec");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+}
@@ -1,10 +1,16 @@
-use clap::Parser;
+use cargo_chec_core::{
+    assert_diff, char_budget, collapse_whitespace, fit_to_budget, git_changed_lines,
+    parse_backtrace, parse_cargo_message, parse_panic, workspace_root, Annotation, ChangedLines,
+    FailedTests, Frame, Level, Outcome, TestTarget,
+};
+use clap::{Parser, ValueEnum};
 use serde_json::{json, Value};
 use std::{
+    collections::{HashMap, HashSet},
     fs,
-    io::{self, BufRead, BufReader, Read, Write},
-    process::{Command, Stdio},
-    thread,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Command, ExitCode, ExitStatus},
 };
 
 #[derive(Parser)]
@@ -20,7 +26,17 @@ enum Cargo {
     long_about = "Runs `cargo test --message-format=json -- --format=json` and transforms the output into a \
     simplified JSON array of test failure strings. Useful for CI/CD pipelines, editors, and AI tools.\n\n\
     All cargo test flags are supported and passed through (e.g. --release, --package, --all-targets).\n\n\
-    Use --input to parse existing cargo test output instead of running cargo test."
+    Use --input to parse existing cargo test output instead of running cargo test.\n\n\
+    Use --changed-since REF to report only compiler warnings on lines changed since a git ref.\n\n\
+    Use --format objects for structured entries; failed tests carry the panic location, message and assert_eq! left/right.\n\n\
+    Big assert_eq! operands are shown as a diff (-left +right) with --diff-context N lines of context.\n\n\
+    Use --backtrace to run with RUST_BACKTRACE=1 and list each failure's workspace frames (no std, core or harness frames).\n\n\
+    Use --format github for GitHub Actions annotation lines; failed tests are annotated at their panic location.\n\n\
+    Use --stream to print each failure as a JSON line as soon as it's reported, followed by a summary line.\n\n\
+    Failed tests are kept in target/cargo-tes/failed-tests.json; --rerun-failed runs only those again.\n\n\
+    Use --retries N to rerun failed tests up to N times; tests that pass on a retry are reported as flaky, not failed.\n\n\
+    Exits 0 when nothing fails, 1 when tests fail or compiler errors are reported, 2 when the build or \
+    cargo tes itself fails for another reason; --no-fail turns 1 into 0."
 )]
 struct TestArgs {
     /// Parse from file or stdin ("-") instead of running cargo test
@@ -31,6 +47,10 @@ struct TestArgs {
     #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
     cargo_args: Vec<String>,
 
+    /// Include compiler warnings in the output
+    #[arg(long)]
+    include_warnings: bool,
+
     /// Include ignored tests in the output as warnings
     #[arg(long)]
     include_ignored: bool,
@@ -38,135 +58,819 @@ struct TestArgs {
     /// Stream raw stderr/stdout to terminal while running
     #[arg(long, short)]
     verbose: bool,
+
+    /// Cap output size in characters, keeping failed tests before suite summaries
+    #[arg(long, value_name = "N")]
+    max_chars: Option<usize>,
+
+    /// Cap output size in tokens (estimated as 4 characters each)
+    #[arg(long, value_name = "N")]
+    max_tokens: Option<usize>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value = "strings")]
+    format: Format,
+
+    /// Only report compiler warnings touching lines changed since REF (git diff REF)
+    #[arg(long, value_name = "REF")]
+    changed_since: Option<String>,
+
+    /// With --changed-since, keep compiler warnings anywhere in a changed file
+    #[arg(long, requires = "changed_since")]
+    whole_files: bool,
+
+    /// Print each failure as a JSON line as soon as it's found, then a summary line
+    #[arg(long, conflicts_with_all = ["max_chars", "max_tokens"])]
+    stream: bool,
+
+    /// Run tests with RUST_BACKTRACE=1 and report each failure's workspace frames as `file:line fn`
+    #[arg(long)]
+    backtrace: bool,
+
+    /// Run only the tests that failed last time, per package and target, matched with --exact
+    #[arg(long, conflicts_with = "input")]
+    rerun_failed: bool,
+
+    /// Rerun failed tests up to N times; those that pass on a retry are reported as flaky
+    #[arg(long, value_name = "N", default_value_t = 0, conflicts_with = "input")]
+    retries: usize,
+
+    /// Lines of context around each change when big assert_eq! operands are shown as a diff
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    diff_context: usize,
+
+    /// Exit 0 even when failures are reported (build and tool failures still exit 2)
+    #[arg(long)]
+    no_fail: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// JSON array of compact strings
+    Strings,
+    /// JSON array of objects with a `kind`; failed tests have name, file, line, col, message, left/right
+    Objects,
+    /// GitHub Actions `::error file=...` annotation lines instead of JSON (ignores the output budget)
+    Github,
+}
+
+/// What produced an output entry. The order is the priority used when
+/// trimming output to --max-chars / --max-tokens.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    CompilerError,
+    BuildFailure,
+    TestFailed,
+    Warning,
+    TestFlaky,
+    TestIgnored,
+    SuiteFailed,
+}
+
+impl Kind {
+    /// Workflow command used for --format github.
+    fn command(self) -> &'static str {
+        match self {
+            Kind::CompilerError | Kind::BuildFailure | Kind::TestFailed | Kind::SuiteFailed => {
+                "error"
+            }
+            Kind::Warning | Kind::TestFlaky => "warning",
+            Kind::TestIgnored => "notice",
+        }
+    }
+
+    /// Whether this entry is a finding that makes cargo tes exit 1.
+    fn is_failure(self) -> bool {
+        !matches!(
+            self,
+            Kind::BuildFailure | Kind::TestFlaky | Kind::TestIgnored
+        )
+    }
+
+    fn category(self) -> &'static str {
+        match self {
+            Kind::CompilerError => "compiler error",
+            Kind::BuildFailure => "build failure",
+            Kind::TestFailed => "failed test",
+            Kind::Warning => "warning",
+            Kind::TestFlaky => "flaky test",
+            Kind::TestIgnored => "ignored test",
+            Kind::SuiteFailed => "suite",
+        }
+    }
+}
+
+/// One output entry: the compact string, its object for --format objects,
+/// and its annotation for --format github.
+struct Entry {
+    kind: Kind,
+    text: String,
+    object: Value,
+    annotation: Annotation,
+    /// The failed tests of a failed suite, to discount flaky ones
+    tests: Vec<String>,
+    /// Target of a failed test or suite, to tell same-named tests apart
+    target: Option<TestTarget>,
+}
+
+impl Entry {
+    /// An entry without a source location.
+    fn new(kind: Kind, text: String) -> Self {
+        let annotation = Annotation::new(kind.command(), None, text.clone());
+        Entry {
+            kind,
+            object: json!({ "kind": kind.category(), "message": text }),
+            text,
+            annotation,
+            tests: Vec::new(),
+            target: None,
+        }
+    }
+}
+
+/// The workspace frames of the backtrace in a failed test's output, as
+/// `file:line fn` with paths relative to `root`.
+fn workspace_backtrace(stdout: &str, root: &Path) -> Vec<String> {
+    let mut kept: Vec<Frame> = Vec::new();
+    for mut frame in parse_backtrace(stdout) {
+        if !frame.is_in_workspace(root) {
+            continue;
+        }
+        // The test harness calls each test through a closure at the fn's own line
+        if kept.last().is_some_and(|prev| {
+            frame.function.strip_suffix("::{{closure}}") == Some(&prev.function)
+        }) {
+            continue;
+        }
+        if let Some(file) = &frame.file {
+            if let Ok(relative) = Path::new(file).strip_prefix(root) {
+                frame.file = Some(relative.display().to_string());
+            }
+        }
+        kept.push(frame);
+    }
+    kept.iter().map(Frame::to_string).collect()
+}
+
+/// A failed test, located where it panicked. Without a recognizable panic
+/// the whole captured output is the message. Big `assert_eq!` operands are
+/// replaced by a diff with `diff_context` lines of context; `backtrace` is
+/// the trimmed backtrace with --backtrace.
+fn failed_test(
+    name: &str,
+    exec_time: f64,
+    stdout: &str,
+    diff_context: usize,
+    backtrace: Option<Vec<String>>,
+) -> Entry {
+    let kind = Kind::TestFailed;
+    let title = Some(format!("Test failed: {}", name));
+    let backtrace = backtrace.filter(|frames| !frames.is_empty());
+    let trailer = match &backtrace {
+        Some(frames) => (
+            format!(" Backtrace: {}", frames.join(" | ")),
+            format!("\nBacktrace:\n  {}", frames.join("\n  ")),
+        ),
+        None => (String::new(), String::new()),
+    };
+    let Some(mut panic) = parse_panic(stdout) else {
+        // The frames stand in for the raw backtrace
+        let stdout = match &backtrace {
+            Some(_) => stdout
+                .split("stack backtrace:\n")
+                .next()
+                .unwrap_or_default(),
+            None => stdout,
+        };
+        let mut object = json!({
+            "kind": kind.category(),
+            "name": name,
+            "exec_time": exec_time,
+            "message": stdout.trim_end(),
+        });
+        if let Some(frames) = &backtrace {
+            object["backtrace"] = json!(frames);
+        }
+        return Entry {
+            kind,
+            text: format!(
+                "Test failed: {} (exec_time: {:.3}s) - {}{}",
+                name,
+                exec_time,
+                collapse_whitespace(stdout),
+                trailer.0
+            ),
+            object,
+            annotation: Annotation::new(
+                kind.command(),
+                title,
+                stdout.trim_end().to_string() + &trailer.1,
+            ),
+            tests: Vec::new(),
+            target: None,
+        };
+    };
+    // Spawned threads panic under their own name
+    let thread = match panic.thread == name {
+        true => String::new(),
+        false => format!(" in thread '{}'", panic.thread),
+    };
+    let diff = match (&panic.left, &panic.right) {
+        (Some(left), Some(right)) => assert_diff(left, right, diff_context),
+        _ => None,
+    };
+    let (summary, message) = match &diff {
+        Some(diff) => {
+            // Only the assertion line; the diff stands in for the operands
+            let head = panic.message.split("\n  left: ").next().unwrap_or_default();
+            let summary = format!(
+                "{} diff (-left +right): {}",
+                collapse_whitespace(head),
+                collapse_whitespace(&diff.lines().collect::<Vec<_>>().join(" | "))
+            );
+            let message = format!("{}\ndiff (-left +right):\n{}", head, diff);
+            panic.message = head.to_string();
+            panic.left = None;
+            panic.right = None;
+            (summary, message)
+        }
+        None => (collapse_whitespace(&panic.message), panic.message.clone()),
+    };
+    let text = format!(
+        "Test failed: {} (exec_time: {:.3}s) at {}:{}:{}{} - {}{}",
+        name, exec_time, panic.file, panic.line, panic.col, thread, summary, trailer.0
+    );
+    let mut object = panic.to_json();
+    if let Some(diff) = diff {
+        object["diff"] = json!(diff);
+    }
+    if let Some(frames) = &backtrace {
+        object["backtrace"] = json!(frames);
+    }
+    object["kind"] = json!(kind.category());
+    object["name"] = json!(name);
+    object["exec_time"] = json!(exec_time);
+    // Whatever the test printed before panicking
+    let output = stdout[..stdout.find("thread '").unwrap_or(0)].trim();
+    if !output.is_empty() {
+        object["output"] = json!(output);
+    }
+    Entry {
+        kind,
+        text,
+        object,
+        annotation: Annotation {
+            file: Some(panic.file),
+            line: Some(panic.line),
+            col: Some(panic.col),
+            ..Annotation::new(kind.command(), title, message + &trailer.1)
+        },
+        tests: Vec::new(),
+        target: None,
+    }
+}
+
+/// A failed suite; `flaky` of its failed tests passed on a retry and aren't
+/// counted as `failed`.
+fn failed_suite(passed: i64, failed: i64, flaky: i64, exec_time: f64, tests: Vec<String>) -> Entry {
+    let flaky_count = match flaky {
+        0 => String::new(),
+        n => format!(", flaky {}", n),
+    };
+    let text = format!(
+        "Suite failed: passed {}, failed {}{} (exec_time: {:.3}s)",
+        passed, failed, flaky_count, exec_time
+    );
+    let mut object = json!({
+        "kind": Kind::SuiteFailed.category(),
+        "passed": passed,
+        "failed": failed,
+        "exec_time": exec_time,
+    });
+    if flaky > 0 {
+        object["flaky"] = json!(flaky);
+    }
+    Entry {
+        object,
+        tests,
+        ..Entry::new(Kind::SuiteFailed, text)
+    }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// A test that failed and then passed on `retry`, reported with its first failure.
+fn flaky_test(failure: &Entry, retry: usize) -> Entry {
+    let kind = Kind::TestFlaky;
+    let name = failure.object["name"].as_str().unwrap_or_default();
+    let mut object = failure.object.clone();
+    object["kind"] = json!(kind.category());
+    object["passed_on_retry"] = json!(retry);
+    Entry {
+        kind,
+        text: failure.text.replacen(
+            &format!("Test failed: {} (", name),
+            &format!("Test flaky: {} (passed on retry {}, ", name, retry),
+            1,
+        ),
+        object,
+        annotation: Annotation {
+            command: kind.command(),
+            title: Some(format!("Test flaky: {} (passed on retry {})", name, retry)),
+            ..failure.annotation.clone()
+        },
+        tests: Vec::new(),
+        target: failure.target.clone(),
+    }
+}
+
+fn main() -> ExitCode {
     let Cargo::Tes(args) = Cargo::parse();
+    let no_fail = args.no_fail;
+    match run(args) {
+        Ok(outcome) => outcome.exit_code(no_fail),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            Outcome::ToolFailure.exit_code(no_fail)
+        }
+    }
+}
+
+/// Matches cargo's compiler messages and libtest events one line at a time,
+/// so entries can be streamed as they arrive as well as collected.
+struct EventMatcher<'a> {
+    args: &'a TestArgs,
+    changed: Option<(ChangedLines, PathBuf)>,
+    /// Whether cargo reported compiler errors at all
+    saw_compiler_errors: bool,
+    /// Passed, failed and ignored tests, summed over finished suites
+    totals: [i64; 3],
+    /// Workspace root for trimming backtraces, with --backtrace
+    backtrace_root: Option<PathBuf>,
+    /// Test binaries by file name, from cargo's compiler-artifact messages
+    artifacts: HashMap<String, TestTarget>,
+    /// Target of the test binary cargo announced last, until its suite starts
+    announced: Option<TestTarget>,
+    /// Target of the test binary being run, when it could be told
+    target: Option<TestTarget>,
+    /// Failed tests by target, kept for --rerun-failed
+    failed: FailedTests,
+    /// Tests that passed, with their target
+    passed: HashSet<(Option<TestTarget>, String)>,
+    /// Failed tests of the suite being run
+    suite_failures: Vec<String>,
+    /// Whether any test binary ran to the end
+    ran_suites: bool,
+}
+
+impl EventMatcher<'_> {
+    /// Notes a `Running …`/`Doc-tests …` line from cargo. Binaries without
+    /// the libtest harness are announced too but start no suite, so only the
+    /// latest announcement counts.
+    fn announce(&mut self, line: &str) {
+        self.announced = self.announced_target(line);
+    }
+
+    /// Whether `test` ran in `target`, passing or failing; in any target if
+    /// that isn't known.
+    fn ran(&self, target: &Option<TestTarget>, test: &str) -> bool {
+        self.failed_in(target, test)
+            || (self.passed.iter())
+                .any(|(t, name)| name == test && (target.is_none() || t == target))
+    }
+
+    /// Whether `test` failed in `target`; in any target if that isn't known.
+    fn failed_in(&self, target: &Option<TestTarget>, test: &str) -> bool {
+        match target {
+            Some(_) => (self.failed.targets.get(target)).is_some_and(|t| t.contains(test)),
+            None => self.failed.targets.values().any(|t| t.contains(test)),
+        }
+    }
+
+    /// The target of the test binary cargo announced with `line`.
+    fn announced_target(&self, line: &str) -> Option<TestTarget> {
+        if let Some(crate_name) = line.strip_prefix("Doc-tests ") {
+            let lib = self
+                .artifacts
+                .values()
+                .find(|t| t.kind == "lib" && t.name.replace('-', "_") == crate_name)?;
+            return Some(TestTarget {
+                kind: "doc".to_string(),
+                ..lib.clone()
+            });
+        }
+        // Running unittests src/lib.rs (target/debug/deps/demo-79a38eab25ff968c)
+        let executable = line.strip_prefix("Running ")?.rsplit_once('(')?.1;
+        let file_name = Path::new(executable.strip_suffix(')')?).file_name()?;
+        self.artifacts.get(file_name.to_str()?).cloned()
+    }
+
+    /// The entry to report for one line of output, if any.
+    fn entry(&mut self, line: &str) -> Option<Entry> {
+        let log: Value = serde_json::from_str(line).ok()?;
+        let args = self.args;
+        if let Some((file_name, target)) = TestTarget::from_artifact(&log) {
+            self.artifacts.insert(file_name, target);
+            return None;
+        }
+        // Handle compiler messages (same as check)
+        if let Some(diag) = parse_cargo_message(&log) {
+            let kind = match diag.level {
+                Level::Error => Kind::CompilerError,
+                Level::Warning if args.include_warnings => Kind::Warning,
+                _ => return None,
+            };
+            self.saw_compiler_errors |= kind == Kind::CompilerError;
+            // Compiler errors keep the tests from running, so they're never filtered out
+            if let Some((changed, root)) = self.changed.as_ref().filter(|_| kind == Kind::Warning) {
+                if !changed.touches_diagnostic(&diag, root, args.whole_files) {
+                    return None;
+                }
+            }
+            let mut object = diag.to_json();
+            object["kind"] = json!(kind.category());
+            Some(Entry {
+                kind,
+                text: diag.to_string(),
+                object,
+                annotation: Annotation::from_diagnostic(&diag),
+                tests: Vec::new(),
+                target: None,
+            })
+        } else if log["type"] == "suite" && log["event"] == "started" {
+            // cargo announces each test binary just before running it
+            self.target = self.announced.take();
+            self.suite_failures.clear();
+            None
+        } else if log["type"] == "test" && log["event"] == "failed" {
+            // Handle failed tests
+            let name = log["name"].as_str()?;
+            self.failed.insert(self.target.clone(), name);
+            self.suite_failures.push(name.to_string());
+            let exec_time = log["exec_time"].as_f64().unwrap_or(0.0);
+            let stdout = log["stdout"].as_str().unwrap_or("");
+            let backtrace =
+                (self.backtrace_root.as_deref()).map(|root| workspace_backtrace(stdout, root));
+            Some(Entry {
+                target: self.target.clone(),
+                ..failed_test(name, exec_time, stdout, args.diff_context, backtrace)
+            })
+        } else if log["type"] == "test" && log["event"] == "ok" {
+            let name = log["name"].as_str()?.to_string();
+            self.passed.insert((self.target.clone(), name));
+            None
+        } else if args.include_ignored && log["type"] == "test" && log["event"] == "ignored" {
+            let name = log["name"].as_str()?;
+            Some(Entry {
+                object: json!({ "kind": Kind::TestIgnored.category(), "name": name }),
+                ..Entry::new(Kind::TestIgnored, format!("Test ignored: {}", name))
+            })
+        } else if log["type"] == "suite" && (log["event"] == "ok" || log["event"] == "failed") {
+            self.ran_suites = true;
+            for (total, key) in self.totals.iter_mut().zip(["passed", "failed", "ignored"]) {
+                *total += log[key].as_i64().unwrap_or(0);
+            }
+            if log["event"] == "ok" {
+                return None;
+            }
+            // Handle failed suites
+            let passed = log["passed"].as_i64()?;
+            let failed = log["failed"].as_i64()?;
+            let exec_time = log["exec_time"].as_f64()?;
+            let tests = std::mem::take(&mut self.suite_failures);
+            Some(Entry {
+                target: self.target.clone(),
+                ..failed_suite(passed, failed, 0, exec_time, tests)
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Prints one entry as soon as it's found, for --stream: a compact JSON line,
+/// or an annotation line with --format github.
+fn stream_entry(entry: &Entry, format: Format) {
+    match format {
+        Format::Strings => {
+            let mut line = json!({ "kind": entry.kind.category(), "message": entry.text });
+            // A flaky test line supersedes the failed test line of the same name
+            if matches!(entry.kind, Kind::TestFailed | Kind::TestFlaky) {
+                line["name"] = entry.object["name"].clone();
+            }
+            println!("{}", line)
+        }
+        Format::Objects => println!("{}", entry.object),
+        Format::Github => println!("{}", entry.annotation),
+    }
+}
+
+/// `line` without the color codes cargo adds with CARGO_TERM_COLOR=always.
+fn strip_ansi(line: &str) -> String {
+    let mut plain = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip to the end of the escape sequence, e.g. `\x1b[1m`
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+/// Runs `cargo test` with the passed-through args plus `selection` (cargo
+/// flags) and `filters` (test binary flags), matching its output as it
+/// arrives. Returns cargo's exit status and stderr lines.
+fn cargo_test(
+    args: &TestArgs,
+    selection: &[String],
+    filters: &[String],
+    matcher: &mut EventMatcher,
+    report: &mut impl FnMut(Entry),
+) -> io::Result<(ExitStatus, Vec<String>)> {
+    // Split args: cargo flags before '--', test flags after
+    let (cargo_flags, test_flags): (Vec<_>, Vec<_>) = args
+        .cargo_args
+        .iter()
+        .partition(|arg| !arg.starts_with("--nocapture") && !arg.starts_with("--show-output"));
+
+    // Retries need every test binary to run, not just those up to the first failure
+    let no_fail_fast = args.retries > 0 && !args.cargo_args.iter().any(|a| a == "--no-fail-fast");
+
+    // cargo's stderr and the test binaries' stdout share one pipe, so each
+    // `Running …` announcement arrives right before the events of its binary
+    let (output, writer) = io::pipe()?;
+    let mut child = Command::new("cargo")
+        .arg("test")
+        .arg("--message-format=json")
+        .args(no_fail_fast.then_some("--no-fail-fast"))
+        .args(selection)
+        .args(cargo_flags)
+        .arg("--")
+        .arg("-Z")
+        .arg("unstable-options")
+        .arg("--format=json")
+        .args(test_flags)
+        .args(filters)
+        .env("CARGO_TERM_COLOR", "always")
+        .envs(args.backtrace.then_some(("RUST_BACKTRACE", "1")))
+        .stdout(writer.try_clone()?)
+        .stderr(writer)
+        .spawn()?;
+
+    // Match JSON lines as they arrive; anything else is cargo's stderr,
+    // streamed to the terminal and collected for error reporting on build failure
+    let mut stderr_lines = Vec::new();
+    for line in BufReader::new(output).lines().map_while(Result::ok) {
+        if line.starts_with('{') {
+            if args.verbose {
+                let _ = writeln!(io::stderr(), "[stdout] {}", line);
+            }
+            if let Some(entry) = matcher.entry(&line) {
+                report(entry);
+            }
+            continue;
+        }
+        let plain = strip_ansi(&line);
+        let plain = plain.trim();
+        if plain.starts_with("Running ") || plain.starts_with("Doc-tests ") {
+            matcher.announce(plain);
+        }
+        if !line.trim().starts_with("Running ") {
+            let _ = writeln!(io::stderr(), "{}", line);
+        }
+        stderr_lines.push(line);
+    }
+
+    let status = child.wait()?;
+    Ok((status, stderr_lines))
+}
 
-    let (json_str, failure_status, stderr_lines) = match &args.input {
+/// Runs `cargo test` once per selection, stopping early on compiler errors.
+/// Returns the exit status and stderr of the first failed run, or else the last.
+fn cargo_tests(
+    args: &TestArgs,
+    runs: &[(Vec<String>, Vec<String>)],
+    matcher: &mut EventMatcher,
+    report: &mut impl FnMut(Entry),
+) -> io::Result<Option<(ExitStatus, Vec<String>)>> {
+    let mut reported: Option<(ExitStatus, Vec<String>)> = None;
+    for (selection, filters) in runs {
+        let run = cargo_test(args, selection, filters, matcher, report)?;
+        let failed = !run.0.success();
+        if reported.as_ref().is_none_or(|(status, _)| status.success()) {
+            reported = Some(run);
+        }
+        // Compiler errors would be the same for every other target
+        if failed && matcher.saw_compiler_errors {
+            break;
+        }
+    }
+    Ok(reported)
+}
+
+/// The cargo and test binary flags running just `failed`, one pair per target.
+fn rerun_selections(failed: &FailedTests) -> Vec<(Vec<String>, Vec<String>)> {
+    (failed.targets.iter())
+        .map(|(target, tests)| {
+            let selection = target.as_ref().map(TestTarget::cargo_args);
+            // Merged doc-tests (edition 2024) never match --exact, but
+            // their full `src/lib.rs - item (line N)` names are as specific
+            let exact = target.as_ref().is_none_or(|t| t.kind != "doc");
+            let filters = exact.then(|| "--exact".to_string()).into_iter();
+            (
+                selection.unwrap_or_default(),
+                filters.chain(tests.iter().cloned()).collect(),
+            )
+        })
+        .collect()
+}
+
+/// The failed tests saved by the last run, for --rerun-failed.
+fn load_failed_tests(args: &TestArgs) -> Result<FailedTests, Box<dyn std::error::Error>> {
+    let path = FailedTests::path(&args.cargo_args)?;
+    let text = fs::read_to_string(&path).map_err(|e| {
+        format!(
+            "no failed tests to rerun in {} ({}); run cargo tes first",
+            path.display(),
+            e
+        )
+    })?;
+    Ok(FailedTests::from_json(&serde_json::from_str(&text)?)?)
+}
+
+/// Saves the failed tests of this run for --rerun-failed.
+fn save_failed_tests(
+    args: &TestArgs,
+    failed: &FailedTests,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = FailedTests::path(&args.cargo_args)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(
+        &path,
+        serde_json::to_string_pretty(&failed.to_json())? + "\n",
+    )?;
+    Ok(())
+}
+
+/// Runs cargo tes and prints its output; returns the outcome that decides the exit code.
+fn run(args: TestArgs) -> Result<Outcome, Box<dyn std::error::Error>> {
+    let changed = match &args.changed_since {
+        Some(git_ref) => Some((
+            git_changed_lines(git_ref)?,
+            workspace_root().or_else(|_| std::env::current_dir())?,
+        )),
+        None => None,
+    };
+    let backtrace_root = match args.backtrace {
+        true => Some(workspace_root().or_else(|_| std::env::current_dir())?),
+        false => None,
+    };
+    let rerun = match args.rerun_failed {
+        true => Some(load_failed_tests(&args)?),
+        false => None,
+    };
+    let mut matcher = EventMatcher {
+        args: &args,
+        changed,
+        saw_compiler_errors: false,
+        totals: [0; 3],
+        backtrace_root,
+        artifacts: HashMap::new(),
+        announced: None,
+        target: None,
+        failed: FailedTests::default(),
+        passed: HashSet::new(),
+        suite_failures: Vec::new(),
+        ran_suites: false,
+    };
+    let mut results: Vec<Entry> = Vec::new();
+    let mut report = |entry: Entry| {
+        if args.stream {
+            stream_entry(&entry, args.format);
+        }
+        results.push(entry);
+    };
+
+    // Failed tests that passed on a retry, with their target, and on which retry
+    let mut flaky: Vec<(Option<TestTarget>, String, usize)> = Vec::new();
+    let (failure_status, stderr_lines) = match &args.input {
         Some(p) if p == "-" => {
             eprintln!("⠿ Reading from stdin...");
-            let mut s = String::new();
-            io::stdin().read_to_string(&mut s)?;
-            (s, None, Vec::new())
+            for line in io::stdin().lock().lines() {
+                if let Some(entry) = matcher.entry(&line?) {
+                    report(entry);
+                }
+            }
+            (None, Vec::new())
         }
         Some(p) => {
             eprintln!("⠿ Reading from file: {}", p);
-            (fs::read_to_string(p)?, None, Vec::new())
+            for line in BufReader::new(fs::File::open(p)?).lines() {
+                if let Some(entry) = matcher.entry(&line?) {
+                    report(entry);
+                }
+            }
+            (None, Vec::new())
         }
         None => {
-            eprintln!("⠿ Running cargo test...");
-            // Split args: cargo flags before '--', test flags after
-            let (cargo_flags, test_flags): (Vec<_>, Vec<_>) =
-                args.cargo_args.iter().partition(|arg| {
-                    !arg.starts_with("--nocapture") && !arg.starts_with("--show-output")
-                });
+            // One cargo test per target with failed tests, or the whole suite
+            let runs = match &rerun {
+                Some(failed) => {
+                    eprintln!("⠿ Rerunning {} failed test(s)...", failed.len());
+                    rerun_selections(failed)
+                }
+                None => {
+                    eprintln!("⠿ Running cargo test...");
+                    vec![(Vec::new(), Vec::new())]
+                }
+            };
+            let mut reported = cargo_tests(&args, &runs, &mut matcher, &mut report)?;
 
-            let mut child = Command::new("cargo")
-                .arg("test")
-                .arg("--message-format=json")
-                .args(cargo_flags)
-                .arg("--")
-                .arg("-Z")
-                .arg("unstable-options")
-                .arg("--format=json")
-                .args(test_flags)
-                .env("CARGO_TERM_COLOR", "always")
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()?;
-
-            let verbose = args.verbose;
-
-            // Capture stderr in a separate thread, streaming to terminal
-            // and collecting lines for error reporting on build failure
-            let stderr = child.stderr.take().expect("capture stderr");
-            let stderr_handle = thread::spawn(move || {
-                let mut captured = Vec::new();
-                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
-                    if !line.trim().starts_with("Running ") {
-                        let _ = writeln!(io::stderr(), "{}", line);
+            // Saved tests that didn't run in their target (e.g. one since renamed)
+            // are looked for in every target
+            if let Some(saved) = rerun.as_ref().filter(|_| !matcher.saw_compiler_errors) {
+                let mut missing = FailedTests::default();
+                for (target, tests) in saved.targets.iter().filter(|(t, _)| t.is_some()) {
+                    for test in tests.iter().filter(|test| !matcher.ran(target, test)) {
+                        missing.insert(None, test);
                     }
-                    captured.push(line);
                 }
-                captured
-            });
-
-            // Collect stdout, optionally streaming to terminal
-            let stdout: Vec<_> = BufReader::new(child.stdout.take().expect("capture stdout"))
-                .lines()
-                .map_while(Result::ok)
-                .inspect(|line| {
-                    if verbose {
-                        let _ = writeln!(io::stderr(), "[stdout] {}", line);
+                if !missing.is_empty() {
+                    let runs = rerun_selections(&missing);
+                    let run = cargo_tests(&args, &runs, &mut matcher, &mut report)?;
+                    if reported.as_ref().is_none_or(|(status, _)| status.success()) {
+                        reported = run;
                     }
-                })
-                .collect();
+                }
+            }
 
-            let status = child.wait()?;
-            let stderr_lines = stderr_handle.join().unwrap_or_default();
-            let json_str = stdout.join("\n");
-            eprintln!("⠿ Parsing test results...");
-            (json_str, Some(status), stderr_lines)
-        }
-    };
-
-    let mut results: Vec<String> = json_str.lines()
-        .filter_map(|l| serde_json::from_str::<Value>(l).ok())
-        .filter_map(|log| {
-            // Handle compiler messages (same as check)
-            if let Some(msg) = log.get("message").filter(|_| log["reason"] == "compiler-message") {
-                let (severity, label) = match msg["level"].as_str()? {
-                    "error" => (5, "Error"),
-                    "warning" if args.include_ignored => (4, "Warning"), // Note: treating warnings as optional
-                    _ => return None
-                };
-                let span = msg["spans"].as_array()?.first()?;
-                let resource = span["file_name"].as_str()?;
-                let (sl, sc, ec) = (span["line_start"].as_i64()?, span["column_start"].as_i64()?, span["column_end"].as_i64()?);
-                let message: String = msg["rendered"].as_str()?.split_whitespace().collect::<Vec<_>>().join(" ");
-
-                let related: Vec<Value> = msg["children"].as_array().unwrap_or(&vec![]).iter().filter_map(|c| {
-                    let sp = c["spans"].as_array()?.first()?;
-                    Some(json!({"message": c["message"].as_str()?, "resource": sp["file_name"].as_str()?,
-                        "startLineNumber": sp["line_start"], "startColumn": sp["column_start"],
-                        "endLineNumber": sp["line_end"], "endColumn": sp["column_end"]}))
-                }).collect();
-
-                let mut out = format!("{} (severity {}) in {}:{}:{}-{}: {}",
-                    label, severity, resource, sl, sc, ec, message);
-                for r in &related {
-                    out.push_str(&format!(" Related: In {}:{}:{}-{}: {}",
-                        r["resource"].as_str().unwrap_or(""), r["startLineNumber"], r["startColumn"],
-                        r["endColumn"], r["message"].as_str().unwrap_or("").split_whitespace().collect::<Vec<_>>().join(" ")));
+            // Tests that pass on a retry are flaky rather than failed
+            let (totals, ran_suites) = (matcher.totals, matcher.ran_suites);
+            for retry in 1..=args.retries {
+                if matcher.failed.is_empty() || matcher.saw_compiler_errors {
+                    break;
+                }
+                eprintln!(
+                    "⠿ Retrying {} failed test(s), {} of {}...",
+                    matcher.failed.len(),
+                    retry,
+                    args.retries
+                );
+                let pending = std::mem::take(&mut matcher.failed);
+                matcher.passed.clear();
+                matcher.ran_suites = false;
+                let runs = rerun_selections(&pending);
+                let run = cargo_tests(&args, &runs, &mut matcher, &mut |_| {})?;
+                if !matcher.ran_suites || matcher.saw_compiler_errors {
+                    matcher.failed = pending;
+                    break;
                 }
-                Some(out)
-            } else if log["type"] == "test" && log["event"] == "failed" {
-                // Handle failed tests
-                let name = log["name"].as_str()?;
-                let exec_time = log["exec_time"].as_f64().unwrap_or(0.0);
-                let stdout = log["stdout"].as_str().unwrap_or("").split_whitespace().collect::<Vec<_>>().join(" ");
-                Some(format!("Test failed: {} (exec_time: {:.3}s) - {}", name, exec_time, stdout))
-            } else if log["type"] == "suite" && log["event"] == "failed" {
-                // Handle failed suites
-                let passed = log["passed"].as_i64()?;
-                let failed = log["failed"].as_i64()?;
-                let exec_time = log["exec_time"].as_f64()?;
-                Some(format!("Suite failed: passed {}, failed {} (exec_time: {:.3}s)", passed, failed, exec_time))
-            } else {
-                None
-            }
-        }).collect();
+                for (target, tests) in &pending.targets {
+                    for test in tests {
+                        if !matcher.ran(target, test) {
+                            // Not run at all, so still failed
+                            matcher.failed.insert(target.clone(), test);
+                        } else if !matcher.failed_in(target, test) {
+                            flaky.push((target.clone(), test.clone(), retry));
+                        }
+                    }
+                }
+                // With every failure flaky, the last retry is the run that counts
+                if matcher.failed.is_empty() {
+                    reported = run;
+                }
+            }
+            // Counts are for the first run; retries only reclassify its failures
+            matcher.totals = totals;
+            matcher.ran_suites = ran_suites;
+
+            let (status, stderr_lines) = reported.unzip();
+            (status, stderr_lines.unwrap_or_default())
+        }
+    };
+
+    // Keep this run's failures for --rerun-failed, unless the tests didn't get to run
+    if args.input.is_none() && matcher.ran_suites && !matcher.saw_compiler_errors {
+        if let Err(e) = save_failed_tests(&args, &matcher.failed) {
+            eprintln!("✗ Couldn't save failed tests: {}", e);
+        }
+    }
+    let saw_compiler_errors = matcher.saw_compiler_errors;
 
     if let Some(status) = failure_status {
         if !status.success() {
             let exit_code = status.code().unwrap_or(-1);
-            results.push(format!("Cargo test failed with exit code {}", exit_code));
+            report(Entry::new(
+                Kind::BuildFailure,
+                format!("Cargo test failed with exit code {}", exit_code),
+            ));
 
             // If no compiler messages were captured in JSON (e.g. build.rs failure),
             // include stderr output so the user sees what went wrong
-            let has_compiler_errors = results.iter().any(|r| r.starts_with("Error (severity"));
-            if !has_compiler_errors {
+            if !saw_compiler_errors {
                 let error_lines: Vec<&str> = stderr_lines
                     .iter()
                     .map(|s| s.as_str())
@@ -179,18 +883,116 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     })
                     .collect();
                 if !error_lines.is_empty() {
-                    let stderr_summary: String = error_lines
-                        .join(" ")
-                        .split_whitespace()
-                        .collect::<Vec<_>>()
-                        .join(" ");
-                    results.push(format!("Build stderr: {}", stderr_summary));
+                    let stderr_summary = collapse_whitespace(&error_lines.join(" "));
+                    report(Entry::new(
+                        Kind::BuildFailure,
+                        format!("Build stderr: {}", stderr_summary),
+                    ));
                 }
             }
         }
     }
 
+    // Flaky tests go after everything that failed
+    for (target, test, retry) in &flaky {
+        let failure = results.iter_mut().find(|e| {
+            e.kind == Kind::TestFailed && e.object["name"] == test.as_str() && e.target == *target
+        });
+        if let Some(failure) = failure {
+            *failure = flaky_test(failure, *retry);
+            if args.stream {
+                stream_entry(failure, args.format);
+            }
+        }
+    }
+    results.sort_by_key(|e| e.kind == Kind::TestFlaky);
+    let streamed = results.len() + flaky.len();
+    // Suites count their flaky tests apart, and didn't fail if all were flaky
+    let is_flaky = |target: &Option<TestTarget>, test: &String| {
+        (flaky.iter()).any(|(t, name, _)| t == target && name == test)
+    };
+    results = results
+        .into_iter()
+        .filter_map(|e| {
+            let flaky_here = e.tests.iter().filter(|t| is_flaky(&e.target, t)).count() as i64;
+            if e.kind != Kind::SuiteFailed || flaky_here == 0 {
+                return Some(e);
+            }
+            let failed = e.object["failed"].as_i64().unwrap_or(0) - flaky_here;
+            (failed > 0).then(|| {
+                failed_suite(
+                    e.object["passed"].as_i64().unwrap_or(0),
+                    failed,
+                    flaky_here,
+                    e.object["exec_time"].as_f64().unwrap_or(0.0),
+                    e.tests,
+                )
+            })
+        })
+        .collect();
+
+    // A test failure or compile error also fails cargo test; only a build
+    // failure without either means the tests couldn't be run for another reason
+    let outcome = if saw_compiler_errors || results.iter().any(|e| e.kind.is_failure()) {
+        Outcome::Found
+    } else if results.iter().any(|e| e.kind == Kind::BuildFailure) {
+        Outcome::ToolFailure
+    } else {
+        Outcome::Clean
+    };
+
+    if args.stream {
+        eprintln!("✓ Streamed {} failure(s)", streamed);
+        if args.format != Format::Github {
+            let [passed, failed, ignored] = matcher.totals;
+            let mut summary = json!({
+                "passed": passed,
+                "failed": failed - flaky.len() as i64,
+                "ignored": ignored,
+                "reported": streamed,
+            });
+            if args.retries > 0 {
+                summary["flaky"] = json!(flaky.len());
+            }
+            println!("{}", json!({ "summary": summary }));
+        }
+        return Ok(outcome);
+    }
+
+    if args.format == Format::Github {
+        eprintln!(
+            "✓ Found {} failure(s), outputting annotations...",
+            results.len()
+        );
+        for entry in &results {
+            println!("{}", entry.annotation);
+        }
+        return Ok(outcome);
+    }
+
     eprintln!("✓ Found {} failure(s), outputting JSON...", results.len());
-    println!("{}", serde_json::to_string(&results)?);
-    Ok(())
+    let render = |e: Entry| match args.format {
+        Format::Objects => e.object,
+        _ => json!(e.text),
+    };
+    let output: Vec<Value> = match char_budget(args.max_chars, args.max_tokens) {
+        Some(max) => {
+            results.sort_by_key(|e| e.kind);
+            let items = results
+                .into_iter()
+                .map(|e| (e.kind.category().to_string(), render(e)))
+                .collect();
+            let (mut kept, omitted) = fit_to_budget(items, max);
+            if !omitted.is_empty() {
+                kept.push(match args.format {
+                    Format::Objects => omitted.to_json(),
+                    _ => json!(omitted.to_string()),
+                });
+            }
+            kept
+        }
+        None => results.into_iter().map(render).collect(),
+    };
+    println!("{}", serde_json::to_string(&output)?);
+    Ok(outcome)
 }
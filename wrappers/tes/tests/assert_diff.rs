@@ -0,0 +1,79 @@
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Returns the path to the cargo-tes binary (debug build).
+fn cargo_tes_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_cargo-tes"))
+}
+
+/// Runs cargo-tes on one failed-test event whose output is `stdout`.
+fn run(stdout: &str, args: &[&str]) -> Vec<Value> {
+    let event = json!({ "type": "test", "name": "snapshot", "event": "failed", "stdout": stdout });
+    let mut child = Command::new(cargo_tes_bin())
+        .args(["tes", "--input", "-"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to run cargo-tes");
+    writeln!(child.stdin.take().unwrap(), "{}", event).unwrap();
+    let output = child.wait_with_output().unwrap();
+    serde_json::from_slice(&output.stdout).expect("stdout should be a JSON array")
+}
+
+fn assert_eq_panic(left: &str, right: &str) -> String {
+    format!(
+        "thread 'snapshot' (1) panicked at tests/snapshot.rs:9:5:\n\
+         assertion `left == right` failed\n  left: {}\n right: {}\n",
+        left, right
+    )
+}
+
+#[test]
+fn big_string_operands_become_a_char_diff() {
+    let base = "lorem ipsum dolor sit amet ".repeat(20);
+    let left = format!("{:?}", format!("{}alpha{}", base, base));
+    let right = format!("{:?}", format!("{}beta{}", base, base));
+    let out = run(&assert_eq_panic(&left, &right), &["--diff-context", "1"]);
+    let text = out[0].as_str().unwrap();
+    assert!(text.ends_with(
+        "assertion `left == right` failed diff (-left +right): \
+         \"… sit amet [-alph-]{+bet+}alorem ips…\""
+    ));
+    assert!(text.len() < 200, "{}", text);
+}
+
+#[test]
+fn big_struct_operands_become_a_line_diff() {
+    let value = |port: u16| {
+        format!(
+            "Config {{ name: \"server\", port: {}, hosts: [\"a.example\", \"b.example\"], \
+             retries: 3, timeout: Some(30) }}",
+            port
+        )
+    };
+    let out = run(
+        &assert_eq_panic(&value(8080), &value(9090)),
+        &["--format", "objects", "--diff-context", "0"],
+    );
+    let failed = &out[0];
+    assert_eq!(failed["message"], "assertion `left == right` failed");
+    assert_eq!(
+        failed["diff"],
+        "  ⋮ 2 unchanged lines\n-     port: 8080,\n+     port: 9090,\n  ⋮ 9 unchanged lines"
+    );
+    assert!(failed.get("left").is_none());
+}
+
+#[test]
+fn small_operands_are_kept() {
+    let out = run(&assert_eq_panic("4", "5"), &["--format", "objects"]);
+    assert_eq!(
+        (out[0]["left"].as_str(), out[0]["right"].as_str()),
+        (Some("4"), Some("5"))
+    );
+    assert!(out[0].get("diff").is_none());
+}
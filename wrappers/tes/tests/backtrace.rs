@@ -0,0 +1,80 @@
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Returns the path to the cargo-tes binary (debug build).
+fn cargo_tes_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_cargo-tes"))
+}
+
+/// A libtest failure event whose output carries a `RUST_BACKTRACE=1` backtrace.
+fn failed_event() -> String {
+    let stdout = "\nthread 'tests::it_fails' (4242) panicked at src/lib.rs:2:5:\n\
+        assertion `left == right` failed\n  left: 1\n right: 2\n\
+        stack backtrace:\n\
+        \x20  0: __rustc::rust_begin_unwind\n\
+        \x20            at /rustc/abc/library/std/src/panicking.rs:689:5\n\
+        \x20  1: core::panicking::panic_fmt\n\
+        \x20            at /rustc/abc/library/core/src/panicking.rs:80:14\n\
+        \x20  2: bt::helper\n\
+        \x20            at ./src/lib.rs:2:5\n\
+        \x20  3: bt::tests::it_fails\n\
+        \x20            at ./src/lib.rs:10:9\n\
+        \x20  4: bt::tests::it_fails::{{closure}}\n\
+        \x20            at ./src/lib.rs:9:18\n\
+        \x20  5: core::ops::function::FnOnce::call_once\n\
+        \x20            at /rustc/abc/library/core/src/ops/function.rs:250:5\n\
+        note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.\n";
+    json!({
+        "type": "test",
+        "name": "tests::it_fails",
+        "event": "failed",
+        "exec_time": 0.001,
+        "stdout": stdout,
+    })
+    .to_string()
+}
+
+fn run(args: &[&str]) -> String {
+    let mut child = Command::new(cargo_tes_bin())
+        .args(["tes", "--input", "-"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run cargo-tes");
+    writeln!(child.stdin.take().unwrap(), "{}", failed_event()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn backtrace_keeps_workspace_frames_only() {
+    let objects: Vec<Value> =
+        serde_json::from_str(&run(&["--backtrace", "--format", "objects"])).unwrap();
+    assert_eq!(
+        objects[0]["backtrace"],
+        json!([
+            "src/lib.rs:2 bt::helper",
+            "src/lib.rs:10 bt::tests::it_fails"
+        ])
+    );
+}
+
+#[test]
+fn backtrace_is_appended_to_strings() {
+    let strings: Vec<String> = serde_json::from_str(&run(&["--backtrace"])).unwrap();
+    assert!(
+        strings[0]
+            .ends_with(" Backtrace: src/lib.rs:2 bt::helper | src/lib.rs:10 bt::tests::it_fails"),
+        "got: {}",
+        strings[0]
+    );
+}
+
+#[test]
+fn backtrace_is_left_out_without_the_flag() {
+    let objects: Vec<Value> = serde_json::from_str(&run(&["--format", "objects"])).unwrap();
+    assert!(objects[0].get("backtrace").is_none());
+}
@@ -5,7 +5,7 @@ use std::time::{Duration, Instant};
 /// Returns the path to the cargo-tes binary (debug build).
 fn cargo_tes_bin() -> PathBuf {
     // Built by `cargo test -p cargo-tes` which compiles the binary
-    let mut path = PathBuf::from(env!("CARGO_BIN_EXE_cargo-tes"));
+    let path = PathBuf::from(env!("CARGO_BIN_EXE_cargo-tes"));
     assert!(path.exists(), "cargo-tes binary not found at {:?}", path);
     path
 }
@@ -42,6 +42,9 @@ fn build_rs_failure_does_not_hang() {
         timeout
     );
 
+    // A build script failure is a build failure, not a test failure
+    assert_eq!(output.status.code(), Some(2));
+
     let stdout = String::from_utf8_lossy(&output.stdout);
     let parsed: Vec<String> =
         serde_json::from_str(&stdout).expect("stdout should be valid JSON array");
@@ -0,0 +1,38 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Returns the path to the cargo-tes binary (debug build).
+fn cargo_tes_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_cargo-tes"))
+}
+
+/// Returns the path to the tools/broken-tests crate, which fails to compile.
+fn broken_tests_dir() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.pop(); // wrappers/
+    path.pop(); // project root
+    path.push("tools/broken-tests");
+    assert!(path.exists(), "broken-tests crate not found at {:?}", path);
+    path
+}
+
+#[test]
+fn compiler_errors_outside_the_diff_are_still_reported() {
+    // Nothing in tools/broken-tests changed since HEAD, but its errors keep
+    // every test from running
+    let output = Command::new(cargo_tes_bin())
+        .args(["tes", "--changed-since", "HEAD"])
+        .current_dir(broken_tests_dir())
+        .output()
+        .expect("failed to run cargo-tes");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let parsed: Vec<String> =
+        serde_json::from_str(&stdout).expect("stdout should be valid JSON array");
+
+    assert!(
+        parsed.iter().any(|s| s.starts_with("Error[E0308]")),
+        "expected the compiler errors, got: {:?}",
+        parsed
+    );
+    assert_eq!(output.status.code(), Some(1));
+}
@@ -0,0 +1,57 @@
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Returns the path to the cargo-tes binary (debug build).
+fn cargo_tes_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_cargo-tes"))
+}
+
+/// Returns the path to the recorded `cargo test` JSON output of tools/demo-outputs.
+fn cargo_test_fixture() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.pop(); // wrappers/
+    path.pop(); // project root
+    path.push("tools/demo-outputs/fixtures/cargo-test.json");
+    assert!(path.exists(), "fixture not found at {:?}", path);
+    path
+}
+
+fn exit_code(args: &[&str]) -> i32 {
+    Command::new(cargo_tes_bin())
+        .arg("tes")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .expect("failed to run cargo-tes")
+        .status
+        .code()
+        .expect("exit code")
+}
+
+#[test]
+fn failed_tests_exit_1() {
+    let fixture = cargo_test_fixture();
+    assert_eq!(exit_code(&["--input", fixture.to_str().unwrap()]), 1);
+}
+
+#[test]
+fn no_fail_exits_0_with_failures() {
+    let fixture = cargo_test_fixture();
+    assert_eq!(
+        exit_code(&["--no-fail", "--input", fixture.to_str().unwrap()]),
+        0
+    );
+}
+
+#[test]
+fn clean_run_exits_0() {
+    assert_eq!(exit_code(&["--input", "-"]), 0);
+}
+
+#[test]
+fn unreadable_input_exits_2() {
+    assert_eq!(
+        exit_code(&["--no-fail", "--input", "does-not-exist.json"]),
+        2
+    );
+}
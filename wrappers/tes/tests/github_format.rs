@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Returns the path to the cargo-tes binary (debug build).
+fn cargo_tes_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_cargo-tes"))
+}
+
+/// Returns the path to the recorded `cargo test` JSON output of tools/demo-outputs.
+fn cargo_test_fixture() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.pop(); // wrappers/
+    path.pop(); // project root
+    path.push("tools/demo-outputs/fixtures/cargo-test.json");
+    assert!(path.exists(), "fixture not found at {:?}", path);
+    path
+}
+
+#[test]
+fn failed_tests_are_annotated_at_panic_location() {
+    let output = Command::new(cargo_tes_bin())
+        .args(["tes", "--format", "github", "--input"])
+        .arg(cargo_test_fixture())
+        .output()
+        .expect("failed to run cargo-tes");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 4, "got: {}", stdout);
+    assert!(lines[..3].iter().all(|l| l.starts_with("::error file=")));
+    assert!(
+        lines.contains(
+            &"::error file=tools/demo-outputs/tests/capture_tests.rs,line=12,col=5,\
+              title=Test failed%3A test_failing_with_stdout::assertion `left == right` failed: \
+              Intentional failure to trigger output%0A  left: 4%0A right: 5"
+        ),
+        "got: {}",
+        stdout
+    );
+    assert_eq!(
+        lines[3],
+        "::error::Suite failed: passed 1, failed 3 (exec_time: 0.000s)"
+    );
+}
@@ -0,0 +1,67 @@
+use serde_json::Value;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Returns the path to the cargo-tes binary (debug build).
+fn cargo_tes_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_cargo-tes"))
+}
+
+/// Returns the path to the recorded `cargo test` JSON output of tools/demo-outputs.
+fn cargo_test_fixture() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.pop(); // wrappers/
+    path.pop(); // project root
+    path.push("tools/demo-outputs/fixtures/cargo-test.json");
+    assert!(path.exists(), "fixture not found at {:?}", path);
+    path
+}
+
+fn run(format: &str) -> Vec<Value> {
+    let output = Command::new(cargo_tes_bin())
+        .args(["tes", "--format", format, "--input"])
+        .arg(cargo_test_fixture())
+        .output()
+        .expect("failed to run cargo-tes");
+    serde_json::from_slice(&output.stdout).expect("stdout should be a JSON array")
+}
+
+#[test]
+fn failed_tests_have_panic_location_and_operands() {
+    let objects = run("objects");
+    let failed = objects
+        .iter()
+        .find(|o| o["name"] == "test_failing_with_stdout")
+        .expect("failed test object");
+    assert_eq!(failed["kind"], "failed test");
+    assert_eq!(failed["thread"], "test_failing_with_stdout");
+    assert_eq!(failed["file"], "tools/demo-outputs/tests/capture_tests.rs");
+    assert_eq!(
+        (failed["line"].as_u64(), failed["col"].as_u64()),
+        (Some(12), Some(5))
+    );
+    assert_eq!(failed["left"], "4");
+    assert_eq!(failed["right"], "5");
+    assert!(failed["output"]
+        .as_str()
+        .unwrap()
+        .starts_with("STDOUT: This is normal output"));
+
+    let suite = objects.last().unwrap();
+    assert_eq!(
+        (suite["kind"].as_str(), suite["failed"].as_i64()),
+        (Some("suite"), Some(3))
+    );
+}
+
+#[test]
+fn strings_point_at_the_panic_without_captured_output() {
+    let strings = run("strings");
+    let failed = strings[2].as_str().unwrap();
+    assert_eq!(
+        failed,
+        "Test failed: test_failing_with_stdout (exec_time: 0.000s) at \
+         tools/demo-outputs/tests/capture_tests.rs:12:5 - assertion `left == right` failed: \
+         Intentional failure to trigger output left: 4 right: 5"
+    );
+}
@@ -0,0 +1,119 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Returns the path to the cargo-tes binary (debug build).
+fn cargo_tes_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_cargo-tes"))
+}
+
+/// Returns the path to the tools/demo-outputs crate.
+fn demo_outputs_dir() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.pop(); // wrappers/
+    path.pop(); // project root
+    path.push("tools/demo-outputs");
+    assert!(path.exists(), "demo-outputs not found at {:?}", path);
+    path
+}
+
+/// A fresh target directory, so the saved failed tests don't leak between tests.
+fn target_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cargo-tes-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn run(target_dir: &PathBuf, args: &[&str]) -> Output {
+    Command::new(cargo_tes_bin())
+        .arg("tes")
+        .args(args)
+        .current_dir(demo_outputs_dir())
+        .env("CARGO_TARGET_DIR", target_dir)
+        // libtest's JSON output is unstable
+        .env("RUSTC_BOOTSTRAP", "1")
+        .output()
+        .expect("failed to run cargo-tes")
+}
+
+#[test]
+fn rerun_failed_without_a_previous_run_is_a_tool_failure() {
+    let output = run(&target_dir("none"), &["--rerun-failed"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no failed tests to rerun"));
+}
+
+#[test]
+fn rerun_failed_runs_only_the_saved_tests_and_updates_them() {
+    let dir = target_dir("rerun");
+    let state = dir.join("cargo-tes/failed-tests.json");
+    fs::create_dir_all(state.parent().unwrap()).unwrap();
+    let saved = json!({
+        "version": 1,
+        "targets": [
+            { "package": "output-demo", "kind": "lib", "name": "output_demo", "tests": ["tests::it_works"] },
+            { "package": "output-demo", "kind": "test", "name": "capture_tests", "tests": ["test_passing_with_output"] },
+        ],
+    });
+    fs::write(&state, saved.to_string()).unwrap();
+
+    let output = run(&dir, &["--rerun-failed", "--stream"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let summary: Value = serde_json::from_str(stdout.lines().last().unwrap()).unwrap();
+    // Just the two tests, not the rest of either target
+    assert_eq!(
+        summary["summary"],
+        json!({ "passed": 2, "failed": 0, "ignored": 0, "reported": 0 })
+    );
+    assert_eq!(output.status.code(), Some(0));
+
+    // Both pass now, so there's nothing left to rerun
+    let saved: Value = serde_json::from_str(&fs::read_to_string(&state).unwrap()).unwrap();
+    assert_eq!(saved["targets"], json!([]));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn failed_tests_are_saved_under_their_own_target_after_a_custom_harness() {
+    let dir = target_dir("harness");
+    fs::create_dir_all(&dir).unwrap();
+    let flaky_file = dir.join("runs");
+    // custom_harness runs first and starts no libtest suite
+    let first = Command::new(cargo_tes_bin())
+        .args(["tes", "--test", "custom_harness", "--test", "flaky_tests"])
+        .current_dir(demo_outputs_dir())
+        .env("CARGO_TARGET_DIR", &dir)
+        .env("CARGO_TES_FLAKY_FILE", &flaky_file)
+        .env("RUSTC_BOOTSTRAP", "1")
+        .output()
+        .expect("failed to run cargo-tes");
+    assert_eq!(first.status.code(), Some(1));
+
+    let state = dir.join("cargo-tes/failed-tests.json");
+    let saved: Value = serde_json::from_str(&fs::read_to_string(&state).unwrap()).unwrap();
+    assert_eq!(
+        saved["targets"],
+        json!([{
+            "package": "output-demo",
+            "kind": "test",
+            "name": "flaky_tests",
+            "tests": ["test_flaky_on_first_run"],
+        }])
+    );
+
+    // The saved target runs the test, which passes on its second run
+    let output = Command::new(cargo_tes_bin())
+        .args(["tes", "--rerun-failed", "--stream"])
+        .current_dir(demo_outputs_dir())
+        .env("CARGO_TARGET_DIR", &dir)
+        .env("CARGO_TES_FLAKY_FILE", &flaky_file)
+        .env("RUSTC_BOOTSTRAP", "1")
+        .output()
+        .expect("failed to run cargo-tes");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let summary: Value = serde_json::from_str(stdout.lines().last().unwrap()).unwrap();
+    assert_eq!(summary["summary"]["passed"], 1);
+    assert_eq!(output.status.code(), Some(0));
+    let _ = fs::remove_dir_all(&dir);
+}
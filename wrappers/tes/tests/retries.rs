@@ -0,0 +1,147 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Returns the path to the cargo-tes binary (debug build).
+fn cargo_tes_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_cargo-tes"))
+}
+
+/// Returns the path to the tools/demo-outputs crate.
+fn demo_outputs_dir() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.pop(); // wrappers/
+    path.pop(); // project root
+    path.push("tools/demo-outputs");
+    assert!(path.exists(), "demo-outputs not found at {:?}", path);
+    path
+}
+
+#[test]
+fn passing_on_retry_is_flaky_not_failed() {
+    let dir = std::env::temp_dir().join(format!("cargo-tes-retries-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let output = Command::new(cargo_tes_bin())
+        .args(["tes", "--retries", "2", "--format", "objects"])
+        .args(["--test", "flaky_tests"])
+        .current_dir(demo_outputs_dir())
+        .env("CARGO_TARGET_DIR", &dir)
+        .env("CARGO_TES_FLAKY_FILE", dir.join("runs"))
+        // libtest's JSON output is unstable
+        .env("RUSTC_BOOTSTRAP", "1")
+        .output()
+        .expect("failed to run cargo-tes");
+
+    let objects: Vec<Value> = serde_json::from_slice(&output.stdout).expect("JSON array");
+    // Only the flaky test: no failed suite or cargo failure once it passed
+    assert_eq!(objects.len(), 1, "got: {:?}", objects);
+    assert_eq!(objects[0]["kind"], "flaky test");
+    assert_eq!(objects[0]["name"], "test_flaky_on_first_run");
+    assert_eq!(objects[0]["passed_on_retry"], 1);
+    assert_eq!(
+        objects[0]["message"],
+        "Intentional failure on the first run"
+    );
+    assert_eq!(output.status.code(), Some(0));
+
+    // Flaky tests aren't left for --rerun-failed
+    let saved: Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("cargo-tes/failed-tests.json")).unwrap())
+            .unwrap();
+    assert_eq!(saved["targets"], json!([]));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn failing_on_every_retry_stays_failed() {
+    let dir = std::env::temp_dir().join(format!("cargo-tes-retries-fail-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let output = Command::new(cargo_tes_bin())
+        .args(["tes", "--retries", "2", "--format", "objects"])
+        .args(["--test", "flaky_tests"])
+        .current_dir(demo_outputs_dir())
+        .env("CARGO_TARGET_DIR", &dir)
+        .env("CARGO_TES_FLAKY_FILE", dir.join("runs"))
+        .env("CARGO_TES_ALWAYS_FAIL", "1")
+        .env("RUSTC_BOOTSTRAP", "1")
+        .output()
+        .expect("failed to run cargo-tes");
+
+    let objects: Vec<Value> = serde_json::from_slice(&output.stdout).expect("JSON array");
+    let of_kind =
+        |kind: &str| -> Vec<&Value> { objects.iter().filter(|o| o["kind"] == kind).collect() };
+    let failed = of_kind("failed test");
+    assert_eq!(failed.len(), 1, "got: {:?}", objects);
+    assert_eq!(failed[0]["name"], "test_failing_on_every_run");
+    // The suite counts the flaky test apart from the failed one
+    let suites = of_kind("suite");
+    assert_eq!(suites.len(), 1, "got: {:?}", objects);
+    assert_eq!(
+        (suites[0]["failed"].as_i64(), suites[0]["flaky"].as_i64()),
+        (Some(1), Some(1))
+    );
+    assert_eq!(of_kind("flaky test")[0]["name"], "test_flaky_on_first_run");
+    assert_eq!(output.status.code(), Some(1));
+
+    // Only the consistent failure is left for --rerun-failed
+    let saved: Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("cargo-tes/failed-tests.json")).unwrap())
+            .unwrap();
+    assert_eq!(
+        saved["targets"][0]["tests"],
+        json!(["test_failing_on_every_run"])
+    );
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn same_named_tests_in_other_targets_stay_apart() {
+    let dir = std::env::temp_dir().join(format!("cargo-tes-retries-twin-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    // test_flaky_on_first_run is flaky in flaky_tests, and fails every run in flaky_twin
+    let output = Command::new(cargo_tes_bin())
+        .args(["tes", "--retries", "2", "--format", "objects"])
+        .args(["--test", "flaky_tests", "--test", "flaky_twin"])
+        .current_dir(demo_outputs_dir())
+        .env("CARGO_TARGET_DIR", &dir)
+        .env("CARGO_TES_FLAKY_FILE", dir.join("runs"))
+        .env("CARGO_TES_ALWAYS_FAIL", "1")
+        .env("RUSTC_BOOTSTRAP", "1")
+        .output()
+        .expect("failed to run cargo-tes");
+
+    let objects: Vec<Value> = serde_json::from_slice(&output.stdout).expect("JSON array");
+    let named = |kind: &str| -> Vec<&Value> {
+        (objects.iter())
+            .filter(|o| o["kind"] == kind && o["name"] == "test_flaky_on_first_run")
+            .collect()
+    };
+    assert_eq!(named("flaky test").len(), 1, "got: {:?}", objects);
+    assert_eq!(named("failed test").len(), 1, "got: {:?}", objects);
+    assert_eq!(
+        named("failed test")[0]["message"],
+        "Intentional failure on every run"
+    );
+    assert_eq!(output.status.code(), Some(1));
+
+    // The flaky test isn't saved; the twin's failure is, under its own target
+    let saved: Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("cargo-tes/failed-tests.json")).unwrap())
+            .unwrap();
+    let target = |name: &str, test: &str| json!({ "package": "output-demo", "kind": "test", "name": name, "tests": [test] });
+    assert_eq!(
+        saved["targets"],
+        json!([
+            target("flaky_tests", "test_failing_on_every_run"),
+            target("flaky_twin", "test_flaky_on_first_run"),
+        ])
+    );
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,74 @@
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Returns the path to the cargo-tes binary (debug build).
+fn cargo_tes_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_cargo-tes"))
+}
+
+/// Returns the path to the recorded `cargo test` JSON output of tools/demo-outputs.
+fn cargo_test_fixture() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.pop(); // wrappers/
+    path.pop(); // project root
+    path.push("tools/demo-outputs/fixtures/cargo-test.json");
+    assert!(path.exists(), "fixture not found at {:?}", path);
+    path
+}
+
+#[test]
+fn stream_prints_one_line_per_failure_then_summary() {
+    let output = Command::new(cargo_tes_bin())
+        .args(["tes", "--stream", "--input"])
+        .arg(cargo_test_fixture())
+        .output()
+        .expect("failed to run cargo-tes");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<Value> = stdout
+        .lines()
+        .map(|l| serde_json::from_str(l).expect("each line should be JSON"))
+        .collect();
+    assert_eq!(lines.len(), 5, "got: {}", stdout);
+    assert!(lines[..3].iter().all(|l| l["kind"] == "failed test"));
+    assert_eq!(lines[3]["kind"], "suite");
+    assert_eq!(lines[4]["summary"]["failed"], 3);
+    assert_eq!(lines[4]["summary"]["reported"], 4);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn stream_reports_failure_before_input_ends() {
+    let mut child = Command::new(cargo_tes_bin())
+        .args(["tes", "--stream", "--input", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to run cargo-tes");
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    writeln!(
+        stdin,
+        r#"{{ "type": "test", "name": "tests::slow", "event": "failed", "stdout": "boom" }}"#
+    )
+    .unwrap();
+    stdin.flush().unwrap();
+
+    // The failure arrives while stdin is still open
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    let failure: Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(failure["kind"], "failed test");
+    assert!(failure["message"].as_str().unwrap().contains("tests::slow"));
+
+    drop(stdin);
+    line.clear();
+    stdout.read_line(&mut line).unwrap();
+    let summary: Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(summary["summary"]["reported"], 1);
+    assert_eq!(child.wait().unwrap().code(), Some(1));
+}